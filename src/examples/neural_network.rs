@@ -2,6 +2,7 @@ extern crate gannai;
 extern crate dot;
 extern crate rand;
 
+use std::collections::HashMap;
 use std::fs::File;
 
 use rand::{XorShiftRng, SeedableRng};
@@ -9,15 +10,22 @@ use rand::{XorShiftRng, SeedableRng};
 use gannai::neural_network::{
     Apply,
     ApplyConf,
+    CrossoverFunction,
     ErrorConf,
     Error,
     Mutator,
     Evolve,
     EvolveConf,
+    FitnessFunction,
     IdGenerator,
+    MetricFunction,
+    MutationConf,
     Sample,
+    SelectionFunction,
     Train,
+    TrainBounds,
     TrainConf,
+    TrainMethod,
 };
 
 fn main() {
@@ -26,6 +34,8 @@ fn main() {
     let apply_conf = ApplyConf {
         group_size: 1000,
         threshold: 1e-4,
+        mode: None,
+        max_visits: None,
     };
     let samples = [
         Sample {input: &[0.6, 0.7, 0.8, 0.9], output: &[0.5, 0.4, 0.3]},
@@ -34,6 +44,8 @@ fn main() {
     let error_conf = ErrorConf {
         apply_conf: &apply_conf,
         samples: &samples,
+        metric: &MetricFunction::Euclidean,
+        custom_activations: &HashMap::new(),
     };
     println!("initial error: {}", network_buf.as_network().error(&error_conf));
     for sample in samples.iter() {
@@ -47,6 +59,11 @@ fn main() {
     let train_conf = TrainConf {
         error_conf: &error_conf,
         max_function_calls_count: 1000,
+        method: TrainMethod::Bobyqa,
+        allow_new_arcs: false,
+        weight_bounds: TrainBounds::default(),
+        bias_bounds: TrainBounds::symmetric(),
+        arc_bounds: HashMap::new(),
     };
     println!("trained error: {}", network_buf.as_network_mut().train(&train_conf));
     for sample in samples.iter() {
@@ -61,11 +78,23 @@ fn main() {
     rng.reseed([1, 1, 1, 1]);
     let mut evolve_conf = EvolveConf {
         train_conf: &train_conf,
+        mutation_conf: &MutationConf::default(),
+        selection: &SelectionFunction::Truncation,
+        crossover: &CrossoverFunction::Union,
+        elitism_count: 0,
+        fitness: &FitnessFunction::Error,
         rng: &mut rng,
         node_id: &mut node_id,
         population_size: 4,
         error: 1e-3,
         iterations_count: 3,
+        validation_error_conf: None,
+        patience: None,
+        checkpoint_interval: 0,
+        checkpoint_writer: None,
+        resume_from: None,
+        observer: None,
+        speciation_conf: None,
     };
     let evolved = mutator.evolve(&mut evolve_conf);
     let evolved_network_buf = evolved.as_network_buf();
@@ -0,0 +1,83 @@
+use super::common::Value;
+
+pub trait Activation: Sync {
+    fn activate(&self, value: Value) -> Value;
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum ActivationFunction {
+    Identity,
+    Sigmoid,
+    Tanh,
+    Relu,
+    Step,
+    Custom(String),
+}
+
+impl Default for ActivationFunction {
+    fn default() -> Self {
+        ActivationFunction::Identity
+    }
+}
+
+impl Activation for ActivationFunction {
+    fn activate(&self, value: Value) -> Value {
+        match *self {
+            ActivationFunction::Identity => value,
+            ActivationFunction::Sigmoid => 1.0 / (1.0 + (-value).exp()),
+            ActivationFunction::Tanh => value.tanh(),
+            ActivationFunction::Relu => value.max(0.0),
+            ActivationFunction::Step => if value > 0.0 { 1.0 } else { 0.0 },
+            ActivationFunction::Custom(ref name) => {
+                panic!("Custom activation function \"{}\" is not registered", name)
+            },
+        }
+    }
+}
+
+impl<F> Activation for F where F: Fn(Value) -> Value + Sync {
+    fn activate(&self, value: Value) -> Value {
+        self(value)
+    }
+}
+
+#[test]
+fn test_identity_should_return_value() {
+    assert_eq!(ActivationFunction::Identity.activate(-0.3), -0.3);
+}
+
+#[test]
+fn test_sigmoid_should_succeed() {
+    assert_eq!(ActivationFunction::Sigmoid.activate(0.0), 0.5);
+    assert_eq!(ActivationFunction::Sigmoid.activate(2.0), 1.0 / (1.0 + (-2.0 as Value).exp()));
+}
+
+#[test]
+fn test_tanh_should_succeed() {
+    assert_eq!(ActivationFunction::Tanh.activate(0.5), (0.5 as Value).tanh());
+}
+
+#[test]
+fn test_relu_should_succeed() {
+    assert_eq!(ActivationFunction::Relu.activate(-0.3), 0.0);
+    assert_eq!(ActivationFunction::Relu.activate(0.3), 0.3);
+}
+
+#[test]
+fn test_step_should_succeed() {
+    assert_eq!(ActivationFunction::Step.activate(-0.3), 0.0);
+    assert_eq!(ActivationFunction::Step.activate(0.0), 0.0);
+    assert_eq!(ActivationFunction::Step.activate(0.3), 1.0);
+}
+
+#[test]
+#[should_panic]
+fn test_unregistered_custom_should_panic() {
+    ActivationFunction::Custom(String::from("square")).activate(0.3);
+}
+
+#[test]
+fn test_closure_should_succeed() {
+    let square = |x: Value| x * x;
+    assert_eq!(square.activate(0.3), 0.3 * 0.3);
+}
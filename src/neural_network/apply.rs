@@ -1,22 +1,72 @@
-use std::collections::BTreeMap;
+extern crate rand;
+extern crate rayon;
 
+use self::rand::{Rng, SeedableRng, XorShiftRng};
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::activation::{Activation, ActivationFunction};
 use super::common::Value;
+use super::matrix::MatrixBuf;
 use super::network::Network;
 
 #[derive(RustcDecodable)]
 pub struct Conf {
     pub group_size: usize,
     pub threshold: Value,
+    pub mode: Option<Mode>,
+    pub max_visits: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum Mode {
+    ValueFlow,
+    FeedForward,
+    Recurrent,
+    /// Solves the value flow as a dense linear system over all nodes: building the application
+    /// takes O(n^2) memory and O(n^3) time, each input takes O(n^2), so use it for small networks.
+    Exact,
+    MonteCarlo(u32),
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::ValueFlow
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub dropped: Value,
+    pub visits: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub mean: Vec<Value>,
+    pub variance: Vec<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    NonLinearActivation(usize),
+    NonConvergent,
+    RecurrentMode,
 }
 
 pub trait Apply {
     fn apply<'r>(&'r self, conf: &'r Conf) -> Application<'r>;
+    fn apply_recurrent<'r>(&'r self) -> RecurrentApplication<'r>;
 }
 
 impl<'network> Apply for Network<'network> {
     fn apply<'r>(&'r self, conf: &'r Conf) -> Application<'r> {
         Application::new(self, conf)
     }
+
+    fn apply_recurrent<'r>(&'r self) -> RecurrentApplication<'r> {
+        RecurrentApplication::new(self)
+    }
 }
 
 struct ValuesGroup {
@@ -27,61 +77,496 @@ struct ValuesGroup {
 pub struct Application<'r> {
     conf: &'r Conf,
     network: &'r Network<'r>,
+    custom_activations: HashMap<String, &'r Activation>,
+    order: Vec<usize>,
+    position: Vec<usize>,
+    system: Option<Result<LinearSystem, Error>>,
 }
 
 impl<'r> Application<'r> {
     pub fn new(network: &'r Network<'r>, conf: &'r Conf) -> Self {
-        Application {network: network, conf: conf}
+        let order = match conf.mode {
+            Some(Mode::FeedForward) => topological_order(network),
+            _ => Vec::new(),
+        };
+        let system = match conf.mode {
+            Some(Mode::Exact) => Some(flow_system(network)),
+            _ => None,
+        };
+        Application {
+            network: network,
+            conf: conf,
+            custom_activations: HashMap::new(),
+            position: positions(&order),
+            order: order,
+            system: system,
+        }
+    }
+
+    pub fn custom_activation(mut self, name: &str, activation: &'r Activation) -> Self {
+        self.custom_activations.insert(String::from(name), activation);
+        self
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if let Some(Mode::Recurrent) = self.conf.mode {
+            return Err(Error::RecurrentMode);
+        }
+        match self.system {
+            Some(Err(ref error)) => Err(error.clone()),
+            _ => Ok(()),
+        }
     }
 
     pub fn perform(&self, values: &[Value]) -> Vec<Value> {
+        match self.try_perform(values) {
+            Ok(v) => v,
+            Err(e) => panic!("Can't apply network: {:?}", e),
+        }
+    }
+
+    pub fn try_perform(&self, values: &[Value]) -> Result<Vec<Value>, Error> {
+        self.try_perform_with_diagnostics(values).map(|x| x.0)
+    }
+
+    pub fn perform_with_diagnostics(&self, values: &[Value]) -> (Vec<Value>, Diagnostics) {
+        match self.try_perform_with_diagnostics(values) {
+            Ok(v) => v,
+            Err(e) => panic!("Can't apply network: {:?}", e),
+        }
+    }
+
+    pub fn try_perform_with_diagnostics(&self, values: &[Value]) -> Result<(Vec<Value>, Diagnostics), Error> {
         assert!(values.len() >= self.network.inputs.len());
+        match self.conf.mode {
+            None | Some(Mode::ValueFlow) => Ok(self.perform_value_flow(values)),
+            Some(Mode::Recurrent) => Err(Error::RecurrentMode),
+            Some(Mode::FeedForward) => {
+                let sums = propagate(self.network, &self.custom_activations, &self.order,
+                                     &self.position, values, None);
+                Ok((outputs(self.network, &sums), Diagnostics::default()))
+            },
+            Some(Mode::Exact) => self.perform_exact(values).map(|x| (x, Diagnostics::default())),
+            Some(Mode::MonteCarlo(seed)) => {
+                let (estimate, diagnostics) = self.perform_monte_carlo(values, seed);
+                Ok((estimate.mean, diagnostics))
+            },
+        }
+    }
+
+    pub fn perform_estimate(&self, values: &[Value]) -> Estimate {
+        assert!(values.len() >= self.network.inputs.len());
+        match self.conf.mode {
+            Some(Mode::MonteCarlo(seed)) => self.perform_monte_carlo(values, seed).0,
+            _ => {
+                let mean = self.perform(values);
+                Estimate {variance: vec![0.0; mean.len()], mean: mean}
+            },
+        }
+    }
+
+    fn perform_exact(&self, values: &[Value]) -> Result<Vec<Value>, Error> {
+        let system = match self.system {
+            Some(Ok(ref v)) => v,
+            Some(Err(ref e)) => return Err(e.clone()),
+            None => unreachable!(),
+        };
+        let mut injected = self.network.biases.to_vec();
+        for (&node, &value) in self.network.inputs.iter().zip(values.iter()) {
+            injected[node] += value;
+        }
+        Ok(outputs(self.network, &system.solve(&injected)))
+    }
+
+    fn perform_value_flow(&self, values: &[Value]) -> (Vec<Value>, Diagnostics) {
         let group_size = self.conf.group_size as Value;
         let mut result: BTreeMap<usize, Value> = self.network.outputs.iter()
             .map(|&x| (x, 0.0)).collect::<_>();
-        {
-            let groups = self.network.inputs.iter().zip(values)
-                .map(|(&node, value)| {
-                    self.perform_one(ValuesGroup {sum: value * group_size, node: node})
-                })
-                .flat_map(|x| x.into_iter());
-            for group in groups {
-                *result.get_mut(&group.node).unwrap() += group.sum;
-            }
+        let mut diagnostics = Diagnostics::default();
+        let mut stack = Vec::new();
+        let biases = self.network.biases.iter()
+            .enumerate()
+            .filter(|&(_, &bias)| bias != 0.0)
+            .map(|(node, &bias)| (node, bias));
+        for (node, value) in self.network.inputs.iter().cloned().zip(values.iter().cloned()).chain(biases) {
+            stack.push(ValuesGroup {sum: value * group_size, node: node});
+            self.flow(&mut stack, &mut result, &mut diagnostics);
         }
-        result.values()
+        diagnostics.dropped /= group_size;
+        let outputs = result.values()
             .map(|x| x / group_size as Value)
-            .collect::<Vec<Value>>()
+            .collect::<Vec<Value>>();
+        (outputs, diagnostics)
     }
 
-    fn perform_one(&self, group: ValuesGroup) -> Vec<ValuesGroup> {
-        if self.network.outputs.contains(&group.node) {
-            vec![group]
-        } else if group.sum > self.conf.threshold {
-            let row = self.network.weights.row(group.node);
-            let nodes = row.iter()
-                .enumerate()
+    fn perform_monte_carlo(&self, values: &[Value], seed: u32) -> (Estimate, Diagnostics) {
+        let mut rng = XorShiftRng::from_seed([
+            seed ^ 0x9E3779B9,
+            seed ^ 0x85EBCA6B,
+            seed ^ 0xC2B2AE35,
+            (seed ^ 0x27D4EB2F) | 1,
+        ]);
+        let particles_count = self.conf.group_size as Value;
+        let mut output_nodes = self.network.outputs.iter().cloned().collect::<Vec<_>>();
+        output_nodes.sort();
+        let position = output_nodes.iter()
+            .enumerate()
+            .map(|(n, &x)| (x, n))
+            .collect::<HashMap<usize, usize>>();
+        let mut mean = vec![0.0; output_nodes.len()];
+        let mut variance = vec![0.0; output_nodes.len()];
+        let mut diagnostics = Diagnostics::default();
+        let biases = self.network.biases.iter()
+            .enumerate()
+            .filter(|&(_, &bias)| bias != 0.0)
+            .map(|(node, &bias)| (node, bias));
+        for (node, value) in self.network.inputs.iter().cloned().zip(values.iter().cloned()).chain(biases) {
+            let mut sums = vec![0.0; output_nodes.len()];
+            let mut squares = vec![0.0; output_nodes.len()];
+            for _ in 0..self.conf.group_size {
+                if let Some((output, value)) = self.walk(&mut rng, node, value, &mut diagnostics) {
+                    sums[position[&output]] += value;
+                    squares[position[&output]] += value * value;
+                }
+            }
+            for n in 0..output_nodes.len() {
+                let source_mean = sums[n] / particles_count;
+                mean[n] += source_mean;
+                variance[n] += (squares[n] / particles_count - source_mean * source_mean) / particles_count;
+            }
+        }
+        diagnostics.dropped /= particles_count;
+        (Estimate {mean: mean, variance: variance}, diagnostics)
+    }
+
+    fn walk(&self, rng: &mut XorShiftRng, mut node: usize, mut value: Value,
+            diagnostics: &mut Diagnostics) -> Option<(usize, Value)> {
+        loop {
+            if self.conf.max_visits.map_or(false, |x| diagnostics.visits >= x) {
+                diagnostics.dropped += value.abs();
+                return None;
+            }
+            diagnostics.visits += 1;
+            if self.network.outputs.contains(&node) {
+                return Some((node, value));
+            }
+            if value.abs() * self.conf.group_size as Value <= self.conf.threshold {
+                diagnostics.dropped += value.abs();
+                return None;
+            }
+            let activated = if self.network.inputs.contains(&node) {
+                value
+            } else {
+                activate(self.network, &self.custom_activations, node, value)
+            };
+            let (count, total) = self.network.weights.row(node)
                 .filter(|&(_, &w)| w.abs() > 0.0)
-                .map(|(n, &w)| (n, w))
-                .collect::<Vec<(usize, Value)>>();
-            let new_sum = group.sum / nodes.len() as Value;
-            nodes.iter()
-                .map(|&(node, weight)| {
-                    self.perform_one(ValuesGroup {sum: new_sum * weight, node: node})
+                .fold((0, 0.0), |(count, total), (_, &w)| (count + 1, total + w.abs()));
+            if count == 0 {
+                diagnostics.dropped += value.abs();
+                return None;
+            }
+            let mut target = rng.gen::<Value>() * total;
+            let mut next = None;
+            for (&n, &w) in self.network.weights.row(node).filter(|&(_, &w)| w.abs() > 0.0) {
+                next = Some((n, w));
+                target -= w.abs();
+                if target < 0.0 {
+                    break;
+                }
+            }
+            let (next_node, weight) = next.unwrap();
+            value = activated * weight.signum() * total / count as Value;
+            node = next_node;
+        }
+    }
+
+    pub fn perform_batch(&self, inputs: &[&[Value]]) -> MatrixBuf<Value> {
+        let outputs = inputs.iter()
+            .map(|x| self.perform(x))
+            .collect::<Vec<_>>();
+        MatrixBuf::from_rows(self.network.outputs.len(), outputs)
+    }
+
+    pub fn par_perform_batch(&self, inputs: &[&[Value]]) -> MatrixBuf<Value> {
+        use self::rayon::prelude::{IntoParallelRefIterator, ParallelIterator, ExactParallelIterator};
+        let mut outputs = Vec::new();
+        inputs.par_iter()
+            .map(|x| self.perform(x))
+            .collect_into(&mut outputs);
+        MatrixBuf::from_rows(self.network.outputs.len(), outputs)
+    }
+
+    fn flow(&self, stack: &mut Vec<ValuesGroup>, result: &mut BTreeMap<usize, Value>,
+            diagnostics: &mut Diagnostics) {
+        while let Some(group) = stack.pop() {
+            if self.conf.max_visits.map_or(false, |x| diagnostics.visits >= x) {
+                diagnostics.dropped += group.sum.abs() + stack.drain(..).map(|x| x.sum.abs()).sum::<Value>();
+                break;
+            }
+            diagnostics.visits += 1;
+            if self.network.outputs.contains(&group.node) {
+                *result.get_mut(&group.node).unwrap() += group.sum;
+            } else if group.sum.abs() > self.conf.threshold {
+                let start = stack.len();
+                stack.extend(self.network.weights.row(group.node)
+                    .filter(|&(_, &w)| w.abs() > 0.0)
+                    .map(|(&n, &w)| ValuesGroup {sum: w, node: n}));
+                if stack.len() == start {
+                    diagnostics.dropped += group.sum.abs();
+                    continue;
+                }
+                let sum = self.activate(&group);
+                let new_sum = sum / (stack.len() - start) as Value;
+                for next in stack[start..].iter_mut() {
+                    next.sum = new_sum * next.sum;
+                }
+                stack[start..].reverse();
+            } else {
+                diagnostics.dropped += group.sum.abs();
+            }
+        }
+    }
+
+    fn activate(&self, group: &ValuesGroup) -> Value {
+        if self.network.inputs.contains(&group.node) {
+            return group.sum;
+        }
+        if let ActivationFunction::Identity = self.network.activations[group.node] {
+            return group.sum;
+        }
+        let group_size = self.conf.group_size as Value;
+        activate(self.network, &self.custom_activations, group.node, group.sum / group_size) * group_size
+    }
+}
+
+pub struct RecurrentApplication<'r> {
+    network: &'r Network<'r>,
+    custom_activations: HashMap<String, &'r Activation>,
+    order: Vec<usize>,
+    position: Vec<usize>,
+    state: Vec<Value>,
+}
+
+impl<'r> RecurrentApplication<'r> {
+    pub fn new(network: &'r Network<'r>) -> Self {
+        let order = topological_order(network);
+        RecurrentApplication {
+            network: network,
+            custom_activations: HashMap::new(),
+            position: positions(&order),
+            order: order,
+            state: vec![0.0; network.activations.len()],
+        }
+    }
+
+    pub fn custom_activation(mut self, name: &str, activation: &'r Activation) -> Self {
+        self.custom_activations.insert(String::from(name), activation);
+        self
+    }
+
+    pub fn state(&self) -> &[Value] {
+        &self.state
+    }
+
+    pub fn step(&mut self, values: &[Value]) -> Vec<Value> {
+        assert!(values.len() >= self.network.inputs.len());
+        self.state = propagate(self.network, &self.custom_activations, &self.order, &self.position,
+                               values, Some(&self.state));
+        outputs(self.network, &self.state)
+    }
+
+    pub fn reset(&mut self) {
+        for value in self.state.iter_mut() {
+            *value = 0.0;
+        }
+    }
+}
+
+fn propagate(network: &Network, custom_activations: &HashMap<String, &Activation>, order: &[usize],
+             position: &[usize], values: &[Value], previous: Option<&[Value]>) -> Vec<Value> {
+    let mut sums = network.biases.to_vec();
+    for (&node, &value) in network.inputs.iter().zip(values.iter()) {
+        sums[node] += value;
+    }
+    if let Some(previous) = previous {
+        for &node in order.iter() {
+            for (&dst, &weight) in network.weights.row(node) {
+                if position[dst] <= position[node] {
+                    sums[dst] += previous[node] * weight;
+                }
+            }
+        }
+    }
+    for &node in order.iter() {
+        if !network.inputs.contains(&node) {
+            sums[node] = activate(network, custom_activations, node, sums[node]);
+        }
+        let value = sums[node];
+        for (&dst, &weight) in network.weights.row(node) {
+            if position[dst] > position[node] {
+                sums[dst] += value * weight;
+            }
+        }
+    }
+    sums
+}
+
+struct LinearSystem {
+    size: usize,
+    values: Vec<Value>,
+    pivots: Vec<usize>,
+}
+
+impl LinearSystem {
+    fn new(size: usize, mut values: Vec<Value>) -> Option<Self> {
+        let mut pivots = (0..size).collect::<Vec<_>>();
+        for column in 0..size {
+            let pivot = (column..size)
+                .max_by(|&l, &r| {
+                    values[l * size + column].abs().partial_cmp(&values[r * size + column].abs()).unwrap()
                 })
-                .flat_map(|x| x.into_iter())
-                .collect::<Vec<_>>()
-        } else {
-            vec![]
+                .unwrap();
+            if values[pivot * size + column] == 0.0 {
+                return None;
+            }
+            if pivot != column {
+                for n in 0..size {
+                    values.swap(pivot * size + n, column * size + n);
+                }
+                pivots.swap(pivot, column);
+            }
+            let diagonal = values[column * size + column];
+            for row in column + 1..size {
+                let factor = values[row * size + column] / diagonal;
+                values[row * size + column] = factor;
+                if factor != 0.0 {
+                    for n in column + 1..size {
+                        values[row * size + n] -= factor * values[column * size + n];
+                    }
+                }
+            }
         }
+        Some(LinearSystem {size: size, values: values, pivots: pivots})
+    }
+
+    fn solve(&self, right: &[Value]) -> Vec<Value> {
+        let size = self.size;
+        let mut result = self.pivots.iter().map(|&x| right[x]).collect::<Vec<_>>();
+        for row in 0..size {
+            for n in 0..row {
+                result[row] -= self.values[row * size + n] * result[n];
+            }
+        }
+        for row in (0..size).rev() {
+            for n in row + 1..size {
+                result[row] -= self.values[row * size + n] * result[n];
+            }
+            result[row] /= self.values[row * size + row];
+        }
+        result
     }
 }
 
+fn flow_system(network: &Network) -> Result<LinearSystem, Error> {
+    let size = network.activations.len();
+    for node in 0..size {
+        if network.inputs.contains(&node) || network.outputs.contains(&node) {
+            continue;
+        }
+        if network.activations[node] != ActivationFunction::Identity {
+            return Err(Error::NonLinearActivation(node));
+        }
+    }
+    let mut system = vec![0.0; size * size];
+    let mut bound = vec![0.0; size * size];
+    for node in 0..size {
+        system[node * size + node] = 1.0;
+        bound[node * size + node] = 1.0;
+    }
+    for src in 0..size {
+        if network.outputs.contains(&src) {
+            continue;
+        }
+        let arcs = network.weights.row(src)
+            .filter(|&(_, &w)| w.abs() > 0.0)
+            .map(|(&n, &w)| (n, w))
+            .collect::<Vec<(usize, Value)>>();
+        let count = arcs.len() as Value;
+        for (dst, weight) in arcs.into_iter() {
+            if !weight.is_finite() {
+                return Err(Error::NonConvergent);
+            }
+            system[dst * size + src] -= weight / count;
+            bound[dst * size + src] -= weight.abs() / count;
+        }
+    }
+    let converges = LinearSystem::new(size, bound)
+        .map(|x| x.solve(&vec![1.0; size]).iter().all(|&x| x > 0.0 && x.is_finite()))
+        .unwrap_or(false);
+    if !converges {
+        return Err(Error::NonConvergent);
+    }
+    LinearSystem::new(size, system).ok_or(Error::NonConvergent)
+}
+
+fn outputs(network: &Network, sums: &[Value]) -> Vec<Value> {
+    let mut outputs = network.outputs.iter().cloned().collect::<Vec<_>>();
+    outputs.sort();
+    outputs.into_iter().map(|x| sums[x]).collect()
+}
+
+fn activate(network: &Network, custom_activations: &HashMap<String, &Activation>, node: usize,
+            value: Value) -> Value {
+    match network.activations[node] {
+        ActivationFunction::Custom(ref name) => match custom_activations.get(name) {
+            Some(activation) => activation.activate(value),
+            None => network.activations[node].activate(value),
+        },
+        ref activation => activation.activate(value),
+    }
+}
+
+fn positions(order: &[usize]) -> Vec<usize> {
+    let mut result = vec![0; order.len()];
+    for (index, &node) in order.iter().enumerate() {
+        result[node] = index;
+    }
+    result
+}
+
+fn topological_order(network: &Network) -> Vec<usize> {
+    let nodes_count = network.activations.len();
+    let mut visited = vec![false; nodes_count];
+    let mut order = Vec::with_capacity(nodes_count);
+    let roots = network.inputs.iter().cloned().chain(0..nodes_count).collect::<Vec<_>>();
+    for root in roots.into_iter() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut stack = vec![(root, network.weights.row(root))];
+        while !stack.is_empty() {
+            let next = stack.last_mut().unwrap().1.next();
+            match next {
+                Some((&dst, _)) => if !visited[dst] {
+                    visited[dst] = true;
+                    stack.push((dst, network.weights.row(dst)));
+                },
+                None => order.push(stack.pop().unwrap().0),
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
 #[test]
 fn test_apply_network_contains_one_arc_with_positive_weight_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let weight = 0.4;
     let weights_values = [
         0.0, weight,
@@ -89,10 +574,14 @@ fn test_apply_network_contains_one_arc_with_positive_weight_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let input = 0.6;
     assert_eq!(&network.apply(&conf).perform(&[input])[..], &[input * weight]);
 }
@@ -102,6 +591,7 @@ fn test_apply_network_contains_one_arc_with_negative_weight_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let weight = -0.4;
     let weights_values = [
         0.0, weight,
@@ -109,19 +599,51 @@ fn test_apply_network_contains_one_arc_with_negative_weight_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let input = 0.6;
     assert_eq!(&network.apply(&conf).perform(&[input])[..], &[input * weight]);
 }
 
+#[test]
+fn test_apply_network_with_negative_weight_to_middle_node_should_propagate_negative_value() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = -0.5;
+    let w23 = 0.4;
+    let weights_values = [
+        0.0, w12, 0.0,
+        0.0, 0.0, w23,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let input = 0.6;
+    assert_eq!(&network.apply(&conf).perform(&[input])[..], &[input * w12 * w23]);
+}
+
 #[test]
 fn test_apply_network_with_two_arcs_and_two_inputs_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w13 = 0.4;
     let w23 = 0.2;
     let weights_values = [
@@ -131,10 +653,14 @@ fn test_apply_network_with_two_arcs_and_two_inputs_should_succeed() {
     ];
     let inputs = [0, 1].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let i1 = 0.6;
     let i2 = 0.7;
     assert_eq!(&network.apply(&conf).perform(&[i1, i2])[..], &[i1 * w13 + i2 * w23]);
@@ -145,6 +671,7 @@ fn test_apply_network_with_two_arcs_and_two_outputs_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w12 = 0.4;
     let w13 = 0.2;
     let weights_values = [
@@ -154,10 +681,14 @@ fn test_apply_network_with_two_arcs_and_two_outputs_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1, 2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let input = 0.6;
     assert_eq!(&network.apply(&conf).perform(&[input])[..],
                &[input * w12 / 2.0, input * w13 / 2.0]);
@@ -168,6 +699,7 @@ fn test_apply_network_with_two_arcs_and_one_middle_node_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w12 = 0.4;
     let w23 = 0.2;
     let weights_values = [
@@ -177,10 +709,14 @@ fn test_apply_network_with_two_arcs_and_one_middle_node_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let input = 0.6;
     assert_eq!(&network.apply(&conf).perform(&[input])[..], &[input * w12 * w23]);
 }
@@ -190,6 +726,7 @@ fn test_apply_network_with_two_arcs_and_self_add_arced_input_node_should_succeed
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w11 = 0.2;
     let w12 = 0.4;
     let weights_values = [
@@ -198,10 +735,14 @@ fn test_apply_network_with_two_arcs_and_self_add_arced_input_node_should_succeed
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1000, threshold: 1e-8};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1000, threshold: 1e-8, mode: None, max_visits: None};
     let input = 0.6;
     let actual = network.apply(&conf).perform(&[input]);
     let expected = [input * w12 * (1.0 + w11 / (2.0 - w11)) / 2.0];
@@ -214,15 +755,644 @@ fn test_apply_network_without_arcs_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let weights_values = [
         0.0, 0.0,
         0.0, 0.0,
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let conf = Conf {group_size: 1000, threshold: 1e-8};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1000, threshold: 1e-8, mode: None, max_visits: None};
     assert_eq!(&network.apply(&conf).perform(&[1.0]), &[0.0]);
 }
+
+#[test]
+fn test_apply_network_with_sigmoid_middle_node_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = 0.4;
+    let w23 = 0.2;
+    let weights_values = [
+        0.0, w12, 0.0,
+        0.0, 0.0, w23,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![
+        ActivationFunction::Identity,
+        ActivationFunction::Sigmoid,
+        ActivationFunction::Identity,
+    ];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let input = 0.6;
+    let expected = [ActivationFunction::Sigmoid.activate(input * w12) * w23];
+    assert_eq!(&network.apply(&conf).perform(&[input])[..], &expected);
+}
+
+#[test]
+fn test_apply_network_with_custom_activation_middle_node_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = 0.4;
+    let w23 = 0.2;
+    let weights_values = [
+        0.0, w12, 0.0,
+        0.0, 0.0, w23,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![
+        ActivationFunction::Identity,
+        ActivationFunction::Custom(String::from("square")),
+        ActivationFunction::Identity,
+    ];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let square = |x: Value| x * x;
+    let input = 0.6;
+    let result = network.apply(&conf).custom_activation("square", &square).perform(&[input]);
+    assert_eq!(&result[..], &[(input * w12).powi(2) * w23]);
+}
+
+#[test]
+fn test_apply_network_with_output_bias_and_zero_input_should_return_bias() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weight = 0.4;
+    let bias = 0.3;
+    let weights_values = [
+        0.0, weight,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0, bias];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    assert_eq!(&network.apply(&conf).perform(&[0.0])[..], &[bias]);
+    assert_eq!(&network.apply(&conf).perform(&[0.5])[..], &[0.5 * weight + bias]);
+}
+
+#[test]
+fn test_apply_network_with_middle_node_bias_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = 0.4;
+    let w23 = 0.2;
+    let bias = 0.3;
+    let weights_values = [
+        0.0, w12, 0.0,
+        0.0, 0.0, w23,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0, bias, 0.0];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let input = 0.6;
+    assert_eq!(&network.apply(&conf).perform(&[input])[..], &[input * w12 * w23 + bias * w23]);
+}
+
+#[test]
+fn test_perform_batch_should_give_same_outputs_as_perform() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.0, 0.4, -0.3,
+        0.0, 0.0, 0.2, 0.5,
+        0.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 0.0,
+    ];
+    let inputs = [0, 1].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2, 3].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(4, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..4).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 4];
+    let biases = vec![0.0; 4];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let application = network.apply(&conf);
+    let values: [&[Value]; 3] = [&[0.6, 0.1], &[0.2, 0.7], &[1.0, 0.0]];
+    let batch = application.perform_batch(&values);
+    let par_batch = application.par_perform_batch(&values);
+    assert_eq!(batch.as_matrix(), par_batch.as_matrix());
+    for (n, x) in values.iter().enumerate() {
+        assert_eq!(batch.as_matrix().row(n), &application.perform(x)[..]);
+    }
+}
+
+#[test]
+fn test_apply_feed_forward_should_compute_activated_weighted_sums() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.0, 0.5, 0.3, 0.0,
+        0.0, 0.0, -0.4, 0.0, 0.0,
+        0.0, 0.0, 0.0, 0.0, 0.7,
+        0.0, 0.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 0.0, 0.0,
+    ];
+    let inputs = [0, 1].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [3, 4].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(5, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..5).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let mut activations = vec![ActivationFunction::Identity; 5];
+    activations[2] = ActivationFunction::Sigmoid;
+    let biases = vec![0.0, 0.0, 0.1, 0.0, -0.2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::FeedForward), max_visits: None};
+    let (first, second) = (0.6, 0.2);
+    let hidden = ActivationFunction::Sigmoid.activate(0.1 + first * 0.5 + second * -0.4);
+    assert_eq!(network.apply(&conf).perform(&[first, second]),
+               vec![first * 0.3, -0.2 + hidden * 0.7]);
+}
+
+#[test]
+fn test_apply_feed_forward_should_ignore_arcs_closing_cycles() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.5, 0.0,
+        0.0, 0.0, 0.4,
+        0.0, 0.3, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::FeedForward), max_visits: None};
+    assert_eq!(network.apply(&conf).perform(&[0.6]), vec![0.6 * 0.5 * 0.4]);
+}
+
+#[test]
+fn test_recurrent_application_should_keep_state_between_steps() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 1.0,
+        0.0, 0.5,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let mut application = network.apply_recurrent();
+    assert_eq!(application.step(&[1.0]), vec![1.0]);
+    assert_eq!(application.step(&[1.0]), vec![1.5]);
+    assert_eq!(application.step(&[0.0]), vec![0.75]);
+    assert_eq!(application.state(), &[0.0, 0.75]);
+    application.reset();
+    assert_eq!(application.step(&[1.0]), vec![1.0]);
+}
+
+#[test]
+fn test_apply_recurrent_mode_should_return_error() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.5,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::Recurrent), max_visits: None};
+    let application = network.apply(&conf);
+    assert_eq!(application.check(), Err(Error::RecurrentMode));
+    assert_eq!(application.try_perform(&[0.6]), Err(Error::RecurrentMode));
+}
+
+#[test]
+fn test_apply_exact_network_with_self_arced_input_node_should_sum_series() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w11 = 0.2;
+    let w12 = 0.4;
+    let weights_values = [
+        w11, w12,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::Exact), max_visits: None};
+    let input = 0.6;
+    let actual = network.apply(&conf).perform(&[input]);
+    let expected = input * w12 / (2.0 - w11);
+    assert!((actual[0] - expected).abs() < 1e-12);
+}
+
+#[test]
+fn test_apply_exact_network_with_cycle_should_match_value_flow() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.5, 0.3, 0.0,
+        0.0, 0.0, 0.6, 0.2,
+        0.0, -0.7, 0.0, 0.9,
+        0.0, 0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [3].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(4, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..4).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 4];
+    let biases = vec![0.0, 0.1, 0.0, 0.0];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let value_flow_conf = Conf {group_size: 1, threshold: 1e-12, mode: None, max_visits: None};
+    let exact_conf = Conf {group_size: 1, threshold: 1e-12, mode: Some(Mode::Exact), max_visits: None};
+    let input = 0.6;
+    let expected = network.apply(&value_flow_conf).perform(&[input]);
+    let actual = network.apply(&exact_conf).perform(&[input]);
+    assert!((actual[0] - expected[0]).abs() < 1e-9);
+}
+
+#[test]
+fn test_apply_exact_network_with_amplifying_cycle_should_return_error() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 3.0, 0.0,
+        2.0, 0.0, 0.4,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::Exact), max_visits: None};
+    let application = network.apply(&conf);
+    assert_eq!(application.check(), Err(Error::NonConvergent));
+    assert_eq!(application.try_perform(&[0.6]), Err(Error::NonConvergent));
+}
+
+#[test]
+fn test_apply_exact_network_with_sigmoid_middle_node_should_return_error() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.5, 0.0,
+        0.0, 0.0, 0.4,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity, ActivationFunction::Sigmoid,
+                           ActivationFunction::Identity];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::Exact), max_visits: None};
+    assert_eq!(network.apply(&conf).check(), Err(Error::NonLinearActivation(1)));
+}
+
+#[test]
+fn test_apply_network_with_input_below_threshold_should_report_dropped_value() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.4,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 10, threshold: 1e-2, mode: None, max_visits: None};
+    let (actual, diagnostics) = network.apply(&conf).perform_with_diagnostics(&[-5e-4]);
+    assert_eq!(&actual[..], &[0.0]);
+    assert_eq!(diagnostics, Diagnostics {dropped: 5e-4, visits: 1});
+}
+
+#[test]
+fn test_apply_network_with_dead_end_middle_node_should_report_dropped_value() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w01 = 0.2;
+    let w02 = 0.4;
+    let weights_values = [
+        0.0, w01, w02,
+        0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let input = 0.6;
+    let conf = Conf {group_size: 1, threshold: 1e-8, mode: None, max_visits: None};
+    let (actual, diagnostics) = network.apply(&conf).perform_with_diagnostics(&[input]);
+    assert!((actual[0] - input / 2.0 * w01).abs() < 1e-12);
+    assert_eq!(diagnostics.visits, 3);
+    assert!((diagnostics.dropped - input / 2.0 * w02).abs() < 1e-12);
+    let conf = Conf {group_size: 10000, threshold: 1e-8, mode: Some(Mode::MonteCarlo(1)), max_visits: None};
+    let (actual, diagnostics) = network.apply(&conf).perform_with_diagnostics(&[input]);
+    assert!((actual[0] - input / 2.0 * w01).abs() < 1e-2);
+    assert!((diagnostics.dropped - input / 2.0 * w02).abs() < 1e-2);
+}
+
+#[test]
+fn test_apply_network_with_self_arced_input_node_should_stop_after_max_visits() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w10 = 0.4;
+    let w11 = 0.2;
+    let weights_values = [
+        0.0, 0.0,
+        w10, w11,
+    ];
+    let inputs = [1].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [0].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-8, mode: None, max_visits: Some(3)};
+    let input = 0.6;
+    let (actual, diagnostics) = network.apply(&conf).perform_with_diagnostics(&[input]);
+    assert_eq!(&actual[..], &[input / 2.0 * w10]);
+    assert_eq!(diagnostics.visits, 3);
+    assert!((diagnostics.dropped - input / 2.0 * w11 / 2.0 * (w10 + w11)).abs() < 1e-12);
+}
+
+#[test]
+fn test_apply_network_with_long_chain_should_not_overflow_stack() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let nodes_count = 100000;
+    let entries = (0..nodes_count - 1).map(|x| (x, x + 1, 1.0)).collect::<Vec<_>>();
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [nodes_count - 1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_entries(nodes_count, entries);
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..nodes_count).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; nodes_count];
+    let biases = vec![0.0; nodes_count];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let (actual, diagnostics) = network.apply(&conf).perform_with_diagnostics(&[0.6]);
+    assert_eq!(&actual[..], &[0.6]);
+    assert_eq!(diagnostics, Diagnostics {dropped: 0.0, visits: nodes_count});
+}
+
+#[test]
+fn test_apply_monte_carlo_network_with_one_arc_should_have_zero_variance() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weight = 0.4;
+    let weights_values = [
+        0.0, weight,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 100, threshold: 1e-3, mode: Some(Mode::MonteCarlo(1)), max_visits: None};
+    let input = 0.6;
+    let estimate = network.apply(&conf).perform_estimate(&[input]);
+    assert!((estimate.mean[0] - input * weight).abs() < 1e-12);
+    assert!(estimate.variance[0].abs() < 1e-12);
+}
+
+#[test]
+fn test_apply_monte_carlo_network_with_two_outputs_should_estimate_value_flow() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = 0.4;
+    let w13 = -0.2;
+    let weights_values = [
+        0.0, w12, w13,
+        0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1, 2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 10000, threshold: 1e-3, mode: Some(Mode::MonteCarlo(1)), max_visits: None};
+    let input = 0.6;
+    let application = network.apply(&conf);
+    let estimate = application.perform_estimate(&[input]);
+    let expected = [input * w12 / 2.0, input * w13 / 2.0];
+    for n in 0..2 {
+        assert!(estimate.variance[n] > 0.0);
+        assert!((estimate.mean[n] - expected[n]).abs() < 4.0 * estimate.variance[n].sqrt());
+    }
+    assert_eq!(application.perform_estimate(&[input]), estimate);
+    assert_eq!(application.perform(&[input]), estimate.mean);
+}
+
+#[test]
+fn test_apply_monte_carlo_network_with_self_arced_input_node_should_estimate_exact_value() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w11 = 0.2;
+    let w12 = 0.4;
+    let weights_values = [
+        w11, w12,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let conf = Conf {group_size: 10000, threshold: 1e-8, mode: Some(Mode::MonteCarlo(7)), max_visits: None};
+    let input = 0.6;
+    let estimate = network.apply(&conf).perform_estimate(&[input]);
+    let expected = input * w12 / (2.0 - w11);
+    assert!((estimate.mean[0] - expected).abs() < 4.0 * estimate.variance[0].sqrt());
+}
+
+#[test]
+fn test_apply_monte_carlo_with_adjacent_seeds_should_give_different_estimates() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 0.4, -0.2,
+        0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1, 2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let estimate = |seed: u32| {
+        let conf = Conf {group_size: 100, threshold: 1e-3, mode: Some(Mode::MonteCarlo(seed)), max_visits: None};
+        network.apply(&conf).perform_estimate(&[0.6])
+    };
+    assert!(estimate(0) != estimate(1));
+    assert!(estimate(1) != estimate(2));
+}
+
+#[test]
+fn test_apply_monte_carlo_with_zero_seed_should_threshold_like_value_flow() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weight = 0.4;
+    let weights_values = [
+        0.0, weight,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let value_flow_conf = Conf {group_size: 10, threshold: 1e-2, mode: None, max_visits: None};
+    let monte_carlo_conf = Conf {group_size: 10, threshold: 1e-2, mode: Some(Mode::MonteCarlo(0)), max_visits: None};
+    for &input in [5e-3, 5e-4].iter() {
+        let (expected, expected_diagnostics) = network.apply(&value_flow_conf).perform_with_diagnostics(&[input]);
+        let (actual, actual_diagnostics) = network.apply(&monte_carlo_conf).perform_with_diagnostics(&[input]);
+        assert!((actual[0] - expected[0]).abs() < 1e-12);
+        assert!((actual_diagnostics.dropped - expected_diagnostics.dropped).abs() < 1e-12);
+    }
+}
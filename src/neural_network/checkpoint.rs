@@ -0,0 +1,156 @@
+extern crate rand;
+
+use self::rand::{Rng, XorShiftRng};
+
+use std::collections::BTreeSet;
+
+use super::activation::ActivationFunction;
+use super::common::{Node, Value};
+use super::graph::Graph;
+use super::id_generator::IdGenerator;
+use super::innovation::Innovations;
+use super::mutator::{Arc, Mutator};
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Checkpoint {
+    pub population: Vec<Individual>,
+    pub iterations_count: usize,
+    pub node_id: IdGenerator,
+    pub rng_state: [u32; 4],
+    pub best: Option<Individual>,
+    pub stagnant_iterations_count: usize,
+    pub innovations: Innovations,
+    pub species: Vec<(Individual, usize)>,
+    pub wall_time: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Individual {
+    inputs: Vec<Node>,
+    outputs: Vec<Node>,
+    nodes: Vec<Node>,
+    arcs: Vec<(Node, Node, Value)>,
+    activations: Vec<(Node, ActivationFunction)>,
+    biases: Vec<(Node, Value)>,
+    error: Value,
+}
+
+impl Individual {
+    pub fn new(mutator: &Mutator, error: Value) -> Individual {
+        let graph = mutator.graph();
+        Individual {
+            inputs: mutator.inputs().iter().cloned().collect(),
+            outputs: mutator.outputs().iter().cloned().collect(),
+            nodes: graph.nodes().keys().cloned().collect(),
+            arcs: graph.arcs().iter()
+                .map(|(&Arc(src, dst), &weight)| (src, dst, weight))
+                .collect(),
+            activations: graph.activations().iter()
+                .map(|(&node, activation)| (node, activation.clone()))
+                .collect(),
+            biases: graph.biases().iter()
+                .map(|(&node, &bias)| (node, bias))
+                .collect(),
+            error: error,
+        }
+    }
+
+    pub fn mutator(&self) -> Mutator {
+        let mut graph = Graph::new();
+        for &node in self.nodes.iter() {
+            graph.add_node(node);
+        }
+        for &(src, dst, weight) in self.arcs.iter() {
+            graph.add_arc(src, dst, weight);
+        }
+        for &(node, ref activation) in self.activations.iter() {
+            graph.set_activation(node, activation.clone());
+        }
+        for &(node, bias) in self.biases.iter() {
+            graph.set_bias(node, bias);
+        }
+        Mutator::from_graph(self.inputs.iter().cloned().collect::<BTreeSet<_>>(),
+                            self.outputs.iter().cloned().collect::<BTreeSet<_>>(),
+                            graph)
+    }
+
+    pub fn error(&self) -> Value {
+        self.error
+    }
+}
+
+pub trait RngState {
+    fn state(&self) -> [u32; 4];
+}
+
+impl RngState for XorShiftRng {
+    fn state(&self) -> [u32; 4] {
+        let mut rng = self.clone();
+        let mut state = [rng.next_u32(), rng.next_u32(), rng.next_u32(), rng.next_u32()];
+        for _ in 0..4 {
+            let u = state[3] ^ state[2] ^ (state[2] >> 19);
+            let t = u ^ (u >> 8) ^ (u >> 16) ^ (u >> 24);
+            let x = t ^ (t << 11) ^ (t << 22);
+            state = [x, state[0], state[1], state[2]];
+        }
+        state
+    }
+}
+
+pub trait CheckpointWriter {
+    fn write(&mut self, checkpoint: &Checkpoint);
+}
+
+impl<F> CheckpointWriter for F where F: FnMut(&Checkpoint) {
+    fn write(&mut self, checkpoint: &Checkpoint) {
+        self(checkpoint)
+    }
+}
+
+#[test]
+fn test_individual_should_preserve_mutator_and_error() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 2, 1, 0.1);
+    let arc = mutator.arcs().nth(0).unwrap().clone();
+    mutator.split(&mut node_id, &arc);
+    mutator.set_activation(Node(3), ActivationFunction::Sigmoid);
+    mutator.set_bias(Node(3), -0.3);
+    let individual = Individual::new(&mutator, 0.1 + 0.2);
+    assert_eq!(individual.mutator(), mutator);
+    assert_eq!(individual.error(), 0.1 + 0.2);
+}
+
+#[test]
+fn test_checkpoint_should_survive_json_roundtrip() {
+    use rustc_serialize::json;
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 1, 1, 0.5);
+    let checkpoint = Checkpoint {
+        population: vec![Individual::new(&mutator, 0.25)],
+        iterations_count: 42,
+        node_id: node_id,
+        rng_state: [1, 2, 3, 4],
+        best: None,
+        stagnant_iterations_count: 0,
+        innovations: Innovations::new(),
+        species: vec![(Individual::new(&mutator, 0.125), 3)],
+        wall_time: 1.5,
+    };
+    let decoded: Checkpoint = json::decode(&json::encode(&checkpoint).unwrap()).unwrap();
+    assert_eq!(decoded, checkpoint);
+    assert_eq!(decoded.population[0].mutator(), mutator);
+}
+
+#[test]
+fn test_rng_state_should_continue_same_sequence() {
+    use self::rand::SeedableRng;
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    assert_eq!(rng.state(), [1, 2, 3, 4]);
+    for _ in 0..10 {
+        rng.next_u32();
+    }
+    let mut restored = XorShiftRng::from_seed(rng.state());
+    for _ in 0..10 {
+        assert_eq!(restored.next_u32(), rng.next_u32());
+    }
+}
@@ -1,5 +1,20 @@
+use std::cmp::Ordering;
+
 pub type Value = f64;
 pub type Weight = Value;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, RustcDecodable, RustcEncodable)]
 pub struct Node(pub usize);
+
+pub fn compare(left: &Value, right: &Value) -> Ordering {
+    left.partial_cmp(right).unwrap_or_else(|| left.is_nan().cmp(&right.is_nan()))
+}
+
+#[test]
+fn test_compare_should_order_nan_last() {
+    use std::f64::{INFINITY, NAN};
+    let mut values = vec![NAN, 1.0, INFINITY, NAN, -1.0];
+    values.sort_by(compare);
+    assert_eq!(&values[..3], &[-1.0, 1.0, INFINITY]);
+    assert!(values[3].is_nan() && values[4].is_nan());
+}
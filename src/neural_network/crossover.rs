@@ -0,0 +1,99 @@
+extern crate rand;
+
+use self::rand::Rng;
+
+use super::mutator::Mutator;
+
+pub trait Crossover: Sync {
+    fn cross(&self, fitter: &Mutator, other: &Mutator, rng: &mut Rng) -> Mutator;
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum CrossoverFunction {
+    Union,
+    Aligned,
+}
+
+impl Default for CrossoverFunction {
+    fn default() -> Self {
+        CrossoverFunction::Union
+    }
+}
+
+impl Crossover for CrossoverFunction {
+    fn cross(&self, fitter: &Mutator, other: &Mutator, rng: &mut Rng) -> Mutator {
+        match *self {
+            CrossoverFunction::Union => fitter.union(other),
+            CrossoverFunction::Aligned => aligned(fitter, other, rng),
+        }
+    }
+}
+
+fn aligned(fitter: &Mutator, other: &Mutator, rng: &mut Rng) -> Mutator {
+    let mut rng = rng;
+    let mut result = fitter.clone();
+    for (arc, &weight) in other.graph().arcs().iter() {
+        if fitter.graph().arcs().contains_key(arc) && (&mut rng).gen() {
+            result.set_weight(arc, weight);
+        }
+    }
+    for node in other.nodes() {
+        if fitter.graph().nodes().contains_key(node) && (&mut rng).gen() {
+            result.set_activation(*node, other.graph().node_activation(node));
+            result.set_bias(*node, other.graph().node_bias(node));
+        }
+    }
+    result
+}
+
+#[test]
+fn test_union_should_take_arcs_of_both_parents() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let fitter = Mutator::new(&mut node_id, 1, 1, 0.2);
+    let mut other = fitter.clone();
+    other.split(&mut node_id, &Arc(Node(0), Node(1)));
+    let child = CrossoverFunction::Union.cross(&fitter, &other, &mut rng);
+    assert_eq!(child.arcs().count(), 3);
+}
+
+#[test]
+fn test_aligned_should_take_disjoint_arcs_only_from_fitter_parent() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let fitter = Mutator::new(&mut node_id, 1, 1, 0.2);
+    let mut other = fitter.clone();
+    other.split(&mut node_id, &Arc(Node(0), Node(1)));
+    let child = CrossoverFunction::Aligned.cross(&fitter, &other, &mut rng);
+    assert_eq!(child, fitter);
+}
+
+#[test]
+fn test_aligned_should_inherit_matching_genes_from_both_parents() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let fitter = Mutator::new(&mut node_id, 1, 1, 0.2);
+    let mut other = fitter.clone();
+    other.set_weight(&Arc(Node(0), Node(1)), 0.4);
+    let weights = (0..100)
+        .map(|_| {
+            let child = CrossoverFunction::Aligned.cross(&fitter, &other, &mut rng);
+            child.graph().arc_weight(&Arc(Node(0), Node(1)))
+        })
+        .collect::<Vec<_>>();
+    assert!(weights.iter().all(|&x| x == 0.2 || x == 0.4));
+    assert!(weights.iter().any(|&x| x == 0.2));
+    assert!(weights.iter().any(|&x| x == 0.4));
+}
@@ -1,4 +1,9 @@
-use super::apply::Apply;
+extern crate rayon;
+
+use std::collections::HashMap;
+
+use super::activation::Activation;
+use super::apply::{Apply, Mode};
 use super::apply;
 use super::common::Value;
 use super::network::Network;
@@ -6,6 +11,8 @@ use super::network::Network;
 pub struct Conf<'r> {
     pub apply_conf: &'r apply::Conf,
     pub samples: &'r [Sample<'r>],
+    pub metric: &'r Metric,
+    pub custom_activations: &'r HashMap<String, &'r Activation>,
 }
 
 pub trait Error {
@@ -14,34 +21,179 @@ pub trait Error {
 
 impl<'network> Error for Network<'network> {
     fn error<'r>(&self, conf: &Conf<'r>) -> Value {
-        conf.samples.iter()
-            .inspect(|&sample| {
-                assert!(sample.input.len() >= self.inputs.len());
-                assert!(sample.output.len() >= self.outputs.len());
-            })
-            .map(|&Sample{input, output: expected}| {
-                self.apply(conf.apply_conf)
-                    .perform(input)
-                    .iter()
-                    .zip(expected.iter())
-                    .map(|(lhs, rhs)| (lhs - rhs).powi(2))
-                    .fold(0.0, |sum, x| sum + x)
-                    .sqrt()
-            })
-            .fold(0.0, |sum, x| sum + x)
+        if let Some(Mode::Recurrent) = conf.apply_conf.mode {
+            return conf.metric.total_error(&sequence_errors(self, conf.samples, conf.metric,
+                                                            conf.custom_activations)[..]);
+        }
+        let inputs = conf.samples.iter()
+            .inspect(|&sample| check_sample(self, sample))
+            .map(|x| x.input)
+            .collect::<Vec<_>>();
+        let application = conf.custom_activations.iter()
+            .fold(self.apply(conf.apply_conf), |application, (name, &activation)| {
+                application.custom_activation(name, activation)
+            });
+        if application.check().is_err() {
+            return ::std::f64::INFINITY;
+        }
+        let actual = application.par_perform_batch(&inputs[..]);
+        let errors = conf.samples.iter()
+            .enumerate()
+            .map(|(n, sample)| conf.metric.sample_error(actual.as_matrix().row(n), sample.output))
+            .collect::<Vec<_>>();
+        conf.metric.total_error(&errors[..])
     }
 }
 
+pub struct SequenceConf<'r> {
+    pub sequences: &'r [&'r [Sample<'r>]],
+    pub metric: &'r Metric,
+    pub custom_activations: &'r HashMap<String, &'r Activation>,
+}
+
+pub trait SequenceError {
+    fn sequence_error<'r>(&self, conf: &SequenceConf<'r>) -> Value;
+}
+
+impl<'network> SequenceError for Network<'network> {
+    fn sequence_error<'r>(&self, conf: &SequenceConf<'r>) -> Value {
+        use self::rayon::prelude::{IntoParallelRefIterator, ParallelIterator, ExactParallelIterator};
+        let mut errors = Vec::new();
+        conf.sequences.par_iter()
+            .map(|samples| sequence_errors(self, samples, conf.metric, conf.custom_activations))
+            .collect_into(&mut errors);
+        let errors = errors.into_iter()
+            .flat_map(|x| x.into_iter())
+            .collect::<Vec<_>>();
+        conf.metric.total_error(&errors[..])
+    }
+}
+
+fn sequence_errors(network: &Network, samples: &[Sample], metric: &Metric,
+                   custom_activations: &HashMap<String, &Activation>) -> Vec<Value> {
+    let mut application = custom_activations.iter()
+        .fold(network.apply_recurrent(), |application, (name, &activation)| {
+            application.custom_activation(name, activation)
+        });
+    samples.iter()
+        .inspect(|&sample| check_sample(network, sample))
+        .map(|&Sample{input, output: expected}| {
+            let actual = application.step(input);
+            metric.sample_error(&actual[..], expected)
+        })
+        .collect()
+}
+
+fn check_sample(network: &Network, sample: &Sample) {
+    assert!(sample.input.len() >= network.inputs.len());
+    assert!(sample.output.len() >= network.outputs.len());
+}
+
 pub struct Sample<'r> {
     pub input: &'r [Value],
     pub output: &'r [Value],
 }
 
+pub trait Metric: Sync {
+    fn sample_error(&self, actual: &[Value], expected: &[Value]) -> Value;
+
+    fn total_error(&self, errors: &[Value]) -> Value {
+        errors.iter().fold(0.0, |sum, x| sum + x)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum MetricFunction {
+    Euclidean,
+    Mse,
+    Mae,
+    Rmse,
+    Huber(Value),
+    CrossEntropy,
+    MaxAbs,
+}
+
+impl Default for MetricFunction {
+    fn default() -> Self {
+        MetricFunction::Euclidean
+    }
+}
+
+impl Metric for MetricFunction {
+    fn sample_error(&self, actual: &[Value], expected: &[Value]) -> Value {
+        let pairs = actual.iter().zip(expected.iter());
+        let count = actual.len().min(expected.len());
+        match *self {
+            MetricFunction::Euclidean => {
+                pairs.map(|(lhs, rhs)| (lhs - rhs).powi(2))
+                    .fold(0.0, |sum, x| sum + x)
+                    .sqrt()
+            },
+            MetricFunction::Mse | MetricFunction::Rmse => {
+                average(pairs.map(|(lhs, rhs)| (lhs - rhs).powi(2)).fold(0.0, |sum, x| sum + x), count)
+            },
+            MetricFunction::Mae => {
+                average(pairs.map(|(lhs, rhs)| (lhs - rhs).abs()).fold(0.0, |sum, x| sum + x), count)
+            },
+            MetricFunction::Huber(delta) => {
+                let sum = pairs.map(|(lhs, rhs)| {
+                        let diff = (lhs - rhs).abs();
+                        if diff <= delta {
+                            0.5 * diff * diff
+                        } else {
+                            delta * (diff - 0.5 * delta)
+                        }
+                    })
+                    .fold(0.0, |sum, x| sum + x);
+                average(sum, count)
+            },
+            MetricFunction::CrossEntropy => {
+                const EPSILON: Value = 1e-12;
+                let sum = pairs.map(|(&lhs, &rhs)| {
+                        let p = lhs.max(EPSILON).min(1.0 - EPSILON);
+                        -(rhs * p.ln() + (1.0 - rhs) * (1.0 - p).ln())
+                    })
+                    .fold(0.0, |sum, x| sum + x);
+                average(sum, count)
+            },
+            MetricFunction::MaxAbs => {
+                pairs.map(|(lhs, rhs)| (lhs - rhs).abs()).fold(0.0, Value::max)
+            },
+        }
+    }
+
+    fn total_error(&self, errors: &[Value]) -> Value {
+        let sum = errors.iter().fold(0.0, |sum, x| sum + x);
+        match *self {
+            MetricFunction::Euclidean => sum,
+            MetricFunction::Rmse => average(sum, errors.len()).sqrt(),
+            MetricFunction::MaxAbs => errors.iter().cloned().fold(0.0, Value::max),
+            _ => average(sum, errors.len()),
+        }
+    }
+}
+
+fn average(sum: Value, count: usize) -> Value {
+    if count > 0 {
+        sum / count as Value
+    } else {
+        0.0
+    }
+}
+
+impl<F> Metric for F where F: Fn(&[Value], &[Value]) -> Value + Sync {
+    fn sample_error(&self, actual: &[Value], expected: &[Value]) -> Value {
+        self(actual, expected)
+    }
+}
+
 #[test]
 fn test_error_network_with_one_arc_and_one_sample_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let weight = 0.4;
     let weights_values = [
         0.0, weight,
@@ -49,24 +201,72 @@ fn test_error_network_with_one_arc_and_one_sample_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let input = 0.5;
     let output = 0.5;
     let samples = [
         Sample {input: &[input], output: &[output]},
     ];
-    let conf = Conf {apply_conf: &apply_conf, samples: &samples};
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &MetricFunction::Euclidean,
+                     custom_activations: &HashMap::new()};
     assert_eq!(network.error(&conf), (input * weight - output).powi(2).sqrt());
 }
 
+#[test]
+fn test_error_network_with_custom_activation_should_use_registered_function() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w12 = 0.4;
+    let w23 = 0.5;
+    let weights_values = [
+        0.0, w12, 0.0,
+        0.0, 0.0, w23,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![
+        ActivationFunction::Identity,
+        ActivationFunction::Custom(String::from("square")),
+        ActivationFunction::Identity,
+    ];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let input = 0.5;
+    let output = 0.5;
+    let samples = [
+        Sample {input: &[input], output: &[output]},
+    ];
+    let square = |x: Value| x * x;
+    let mut custom_activations: HashMap<String, &Activation> = HashMap::new();
+    custom_activations.insert(String::from("square"), &square);
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &MetricFunction::Euclidean,
+                     custom_activations: &custom_activations};
+    assert_eq!(network.error(&conf), ((input * w12).powi(2) * w23 - output).powi(2).sqrt());
+}
+
 #[test]
 fn test_error_network_with_one_arc_and_two_samples_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w = 0.4;
     let weights_values = [
         0.0, w,
@@ -74,10 +274,14 @@ fn test_error_network_with_one_arc_and_two_samples_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let i1 = 0.4;
     let i2 = 0.6;
     let o1 = 0.5;
@@ -86,15 +290,18 @@ fn test_error_network_with_one_arc_and_two_samples_should_succeed() {
         Sample {input: &[i1], output: &[o1]},
         Sample {input: &[i2], output: &[o2]},
     ];
-    let conf = Conf {apply_conf: &apply_conf, samples: &samples};
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &MetricFunction::Euclidean,
+                     custom_activations: &HashMap::new()};
     assert_eq!(network.error(&conf), (i1 * w - o1).powi(2).sqrt() + (i2 * w - o2).powi(2).sqrt());
 }
 
 #[test]
 fn test_error_network_with_two_arcs_and_two_outputs_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
     use super::common::Node;
     use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
     let w12 = 0.4;
     let w13 = 0.2;
     let weights_values = [
@@ -104,17 +311,167 @@ fn test_error_network_with_two_arcs_and_two_outputs_should_succeed() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1, 2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes};
-    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3};
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
     let i = 0.4;
     let o1 = 0.4;
     let o2 = 0.6;
     let samples = [
         Sample {input: &[i], output: &[o1, o2]},
     ];
-    let conf = Conf {apply_conf: &apply_conf, samples: &samples};
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &MetricFunction::Euclidean,
+                     custom_activations: &HashMap::new()};
     assert_eq!(network.error(&conf),
                ((i * w12 / 2.0 - o1).powi(2) + (i * w13 / 2.0 - o2).powi(2)).sqrt());
 }
+
+#[test]
+fn test_error_network_with_one_arc_and_two_samples_and_mse_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w = 0.5;
+    let weights_values = [
+        0.0, w,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let samples = [
+        Sample {input: &[0.5], output: &[0.5]},
+        Sample {input: &[1.0], output: &[0.0]},
+    ];
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &MetricFunction::Mse,
+                     custom_activations: &HashMap::new()};
+    assert_eq!(network.error(&conf), (0.25f64.powi(2) + 0.5f64.powi(2)) / 2.0);
+}
+
+#[test]
+fn test_error_network_with_closure_metric_should_sum_sample_errors() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let w = 0.5;
+    let weights_values = [
+        0.0, w,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: None, max_visits: None};
+    let samples = [
+        Sample {input: &[0.5], output: &[0.5]},
+        Sample {input: &[1.0], output: &[0.0]},
+    ];
+    let metric = |actual: &[Value], expected: &[Value]| (actual[0] - expected[0]).abs();
+    let conf = Conf {apply_conf: &apply_conf, samples: &samples, metric: &metric,
+                     custom_activations: &HashMap::new()};
+    assert_eq!(network.error(&conf), 0.25 + 0.5);
+}
+
+#[test]
+fn test_mae_should_succeed() {
+    let metric = MetricFunction::Mae;
+    assert_eq!(metric.sample_error(&[0.5, 0.25], &[0.0, 0.0]), 0.375);
+    assert_eq!(metric.total_error(&[0.5, 0.25]), 0.375);
+}
+
+#[test]
+fn test_rmse_should_succeed() {
+    let metric = MetricFunction::Rmse;
+    assert_eq!(metric.sample_error(&[1.0, 0.0], &[0.0, 1.0]), 1.0);
+    assert_eq!(metric.total_error(&[1.0, 0.25]), (1.25f64 / 2.0).sqrt());
+}
+
+#[test]
+fn test_huber_should_be_quadratic_for_small_and_linear_for_large_differences() {
+    let metric = MetricFunction::Huber(1.0);
+    assert_eq!(metric.sample_error(&[0.5], &[0.0]), 0.125);
+    assert_eq!(metric.sample_error(&[3.0], &[0.0]), 2.5);
+}
+
+#[test]
+fn test_cross_entropy_should_succeed() {
+    let metric = MetricFunction::CrossEntropy;
+    assert_eq!(metric.sample_error(&[0.5], &[1.0]), -(0.5f64.ln()));
+    assert!(metric.sample_error(&[0.0], &[1.0]).is_finite());
+}
+
+#[test]
+fn test_max_abs_should_succeed() {
+    let metric = MetricFunction::MaxAbs;
+    assert_eq!(metric.sample_error(&[0.5, -0.75], &[0.0, 0.0]), 0.75);
+    assert_eq!(metric.total_error(&[0.5, 0.75, 0.25]), 0.75);
+}
+
+#[test]
+fn test_averaging_metrics_should_return_zero_for_empty_values() {
+    for metric in [MetricFunction::Mse, MetricFunction::Mae, MetricFunction::Rmse,
+                   MetricFunction::Huber(1.0), MetricFunction::CrossEntropy].iter() {
+        assert_eq!(metric.sample_error(&[], &[]), 0.0);
+        assert_eq!(metric.total_error(&[]), 0.0);
+    }
+}
+
+#[test]
+fn test_sequence_error_should_depend_on_previous_samples() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use super::activation::ActivationFunction;
+    use super::common::Node;
+    use super::matrix::Matrix;
+    use super::sparse_matrix::SparseMatrixBuf;
+    let weights_values = [
+        0.0, 1.0,
+        0.0, 0.5,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
+    let network = Network {inputs: &inputs, outputs: &outputs, weights: weights, nodes: &nodes,
+                           activations: &activations, biases: &biases};
+    let first = [
+        Sample {input: &[1.0], output: &[1.0]},
+        Sample {input: &[1.0], output: &[1.5]},
+        Sample {input: &[0.0], output: &[0.75]},
+    ];
+    let second = [
+        Sample {input: &[1.0], output: &[1.0]},
+        Sample {input: &[0.0], output: &[0.0]},
+    ];
+    let conf = SequenceConf {sequences: &[&first, &second], metric: &MetricFunction::Euclidean,
+                             custom_activations: &HashMap::new()};
+    assert_eq!(network.sequence_error(&conf), 0.5);
+    let apply_conf = apply::Conf {group_size: 1, threshold: 1e-3, mode: Some(Mode::Recurrent), max_visits: None};
+    let conf = Conf {apply_conf: &apply_conf, samples: &first, metric: &MetricFunction::Euclidean,
+                     custom_activations: &HashMap::new()};
+    assert_eq!(network.error(&conf), 0.0);
+}
@@ -4,145 +4,403 @@ extern crate rayon;
 
 use self::itertools::Itertools;
 
-use self::rand::Rng;
+use self::rand::{Rng, SeedableRng};
 
+use std::time::Instant;
+
+use super::checkpoint::{Checkpoint, CheckpointWriter, Individual, RngState};
+use super::crossover::Crossover;
+use super::mutation;
 use super::train;
 use super::train::Train;
+use super::error;
 use super::error::Error;
-use super::common::{Node, Value};
-use super::mutator::{Arc, Mutator};
+use super::fitness::Fitness;
+use super::common::Value;
+use super::mutator::Mutator;
 use super::network::NetworkBuf;
 use super::id_generator::IdGenerator;
+use super::innovation::Innovations;
+use super::observer::{Observer, Stats};
+use super::selection::Selection;
+use super::speciation;
+use super::speciation::{Speciation, Species};
 
-pub struct Conf<'r, RngT: 'r + Rng> {
+pub struct Conf<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState> {
     pub train_conf: &'r train::Conf<'r>,
+    pub mutation_conf: &'r mutation::Conf,
+    pub selection: &'r Selection,
+    pub crossover: &'r Crossover,
+    pub elitism_count: usize,
+    pub fitness: &'r Fitness,
     pub rng: &'r mut RngT,
     pub node_id: &'r mut IdGenerator,
     pub population_size: usize,
     pub error: Value,
     pub iterations_count: usize,
+    pub validation_error_conf: Option<&'r error::Conf<'r>>,
+    pub patience: Option<usize>,
+    pub checkpoint_interval: usize,
+    pub checkpoint_writer: Option<&'r mut CheckpointWriter>,
+    pub resume_from: Option<&'r Checkpoint>,
+    pub observer: Option<&'r mut Observer>,
+    pub speciation_conf: Option<&'r speciation::Conf>,
 }
 
 pub trait Evolve {
-    fn evolve<'r, RngT: 'r + Rng>(&self, conf: &'r mut Conf<'r, RngT>) -> Self;
+    fn evolve<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self;
 }
 
 impl Evolve for NetworkBuf {
-    fn evolve<'r, RngT: 'r + Rng>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
+    fn evolve<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
         Mutator::from_network(&self.as_network()).evolve(conf).as_network_buf()
     }
 }
 
 impl Evolve for Mutator {
-    fn evolve<'r, RngT: 'r + Rng>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
+    fn evolve<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
         Evolution::new(conf, self).perform()
     }
 }
 
-trait MutatorRandom {
-    fn random_node<'r, RngT: 'r + Rng>(&self, rng: &mut RngT) -> Node;
-    fn random_arc<'r, RngT: 'r + Rng>(&self, rng: &mut RngT) -> Arc;
+pub trait EvolveFront: Sized {
+    fn evolve_front<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Vec<Self>;
 }
 
-impl MutatorRandom for Mutator {
-    fn random_node<'r, RngT: 'r + Rng>(&self, rng: &mut RngT) -> Node {
-        let nth = rng.gen_range(0, self.nodes().len());
-        *self.nodes().nth(nth).unwrap()
+impl EvolveFront for NetworkBuf {
+    fn evolve_front<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Vec<Self> {
+        Mutator::from_network(&self.as_network()).evolve_front(conf).into_iter()
+            .map(|x| x.as_network_buf())
+            .collect()
     }
+}
 
-    fn random_arc<'r, RngT: 'r + Rng>(&self, rng: &mut RngT) -> Arc {
-        let nth = rng.gen_range(0, self.arcs().len());
-        self.arcs().nth(nth).unwrap().clone()
+impl EvolveFront for Mutator {
+    fn evolve_front<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Vec<Self> {
+        Evolution::new(conf, self).perform_front()
     }
 }
 
-struct Mutation {
+#[derive(Clone)]
+pub struct Mutation {
     mutator: Mutator,
     error: Value,
 }
 
-struct Evolution<'c, 'f, RngT: 'c + Rng> {
+pub enum Step {
+    Continue(Vec<Mutation>),
+    Done(Vec<Mutation>),
+}
+
+pub struct Evolution<'c, 'f, RngT: 'c + Rng + SeedableRng<[u32; 4]> + RngState> {
     conf: &'c mut Conf<'c, RngT>,
     mutator: &'f Mutator,
     iterations_count: usize,
+    best: Option<Mutation>,
+    stagnant_iterations_count: usize,
+    checkpoint_iterations_count: Option<usize>,
+    started: Instant,
+    elapsed_before: Value,
+    innovations: Innovations,
+    speciation: Speciation,
 }
 
-impl<'c, 'f, RngT: 'c + Rng> Evolution<'c, 'f, RngT> {
+impl<'c, 'f, RngT: 'c + Rng + SeedableRng<[u32; 4]> + RngState> Evolution<'c, 'f, RngT> {
     pub fn new(conf: &'c mut Conf<'c, RngT>, mutator: &'f Mutator) -> Self {
         assert!(conf.population_size > 1);
         assert!(conf.population_size % 2 == 0);
-        Evolution {conf: conf, mutator: mutator, iterations_count: 0}
+        Evolution {
+            conf: conf,
+            mutator: mutator,
+            iterations_count: 0,
+            best: None,
+            stagnant_iterations_count: 0,
+            checkpoint_iterations_count: None,
+            started: Instant::now(),
+            elapsed_before: 0.0,
+            innovations: Innovations::new(),
+            speciation: Speciation::new(),
+        }
     }
 
     pub fn perform(&mut self) -> Mutator {
+        match self.run() {
+            Some(evolved) => self.finish(evolved),
+            None => self.mutator.clone(),
+        }
+    }
+
+    pub fn perform_front(&mut self) -> Vec<Mutator> {
+        match self.run() {
+            Some(evolved) => self.front(evolved),
+            None => vec![self.mutator.clone()],
+        }
+    }
+
+    fn run(&mut self) -> Option<Vec<Mutation>> {
+        self.initialize().map(|x| self.evolve(x))
+    }
+
+    pub fn initialize(&mut self) -> Option<Vec<Mutation>> {
+        if let Some(checkpoint) = self.conf.resume_from {
+            return Some(self.resume(checkpoint));
+        }
         if !self.begin() {
-            return self.mutator.clone();
+            return None;
         }
+        self.innovations.register_mutator(self.mutator);
         let error = self.mutator.as_network_buf().as_network().error(self.conf.train_conf.error_conf);
+        if let Some(validation_error_conf) = self.conf.validation_error_conf {
+            let validation_error = self.mutator.as_network_buf().as_network()
+                .error(validation_error_conf);
+            self.best = Some(Mutation {mutator: self.mutator.clone(), error: validation_error});
+        }
         let population = (0..self.conf.population_size)
             .map(|_| Mutation {mutator: self.mutator.clone(), error: error})
             .collect::<Vec<_>>();
-        let evolved = self.evolve(population);
-        self.select_one(evolved)
+        Some(population)
+    }
+
+    fn resume(&mut self, checkpoint: &Checkpoint) -> Vec<Mutation> {
+        self.iterations_count = checkpoint.iterations_count;
+        self.stagnant_iterations_count = checkpoint.stagnant_iterations_count;
+        self.checkpoint_iterations_count = Some(checkpoint.iterations_count);
+        self.best = checkpoint.best.as_ref()
+            .map(|x| Mutation {mutator: x.mutator(), error: x.error()});
+        *self.conf.node_id = checkpoint.node_id.clone();
+        self.conf.rng.reseed(checkpoint.rng_state);
+        self.started = Instant::now();
+        self.elapsed_before = checkpoint.wall_time;
+        self.innovations = checkpoint.innovations.clone();
+        self.speciation = Speciation::from_species(checkpoint.species.iter()
+            .map(|&(ref x, stagnant_iterations_count)| Species {
+                representative: x.mutator(),
+                best_error: x.error(),
+                stagnant_iterations_count: stagnant_iterations_count,
+            })
+            .collect());
+        checkpoint.population.iter()
+            .map(|x| Mutation {mutator: x.mutator(), error: x.error()})
+            .collect()
+    }
+
+    pub fn finish(&mut self, population: Vec<Mutation>) -> Mutator {
+        match self.best.take() {
+            Some(best) => best.mutator,
+            None => self.select_one(population),
+        }
+    }
+
+    fn front(&self, population: Vec<Mutation>) -> Vec<Mutator> {
+        let pairs = population.iter().map(|x| (&x.mutator, x.error)).collect::<Vec<_>>();
+        let mut result: Vec<&Mutation> = Vec::new();
+        for index in self.conf.fitness.front(&pairs) {
+            if result.iter().all(|x| x.mutator != population[index].mutator) {
+                result.push(&population[index]);
+            }
+        }
+        let errors = match self.conf.validation_error_conf {
+            Some(validation_error_conf) => result.iter()
+                .map(|x| x.mutator.as_network_buf().as_network().error(validation_error_conf))
+                .collect::<Vec<_>>(),
+            None => result.iter().map(|x| x.error).collect(),
+        };
+        (0..result.len())
+            .sorted_by(|&l, &r| errors[l].partial_cmp(&errors[r]).unwrap())
+            .into_iter()
+            .map(|x| result[x].mutator.clone())
+            .collect()
     }
 
     fn evolve(&mut self, mut population: Vec<Mutation>) -> Vec<Mutation> {
         loop {
-            let mutated = self.mutate(population);
-            self.iterations_count += 1;
-            if self.terminate(&mutated) {
-                return mutated;
+            match self.step(population) {
+                Step::Continue(x) => population = x,
+                Step::Done(x) => return x,
             }
-            let propagated = self.propagate(mutated);
-            population = self.select(propagated);
         }
     }
 
+    pub fn step(&mut self, population: Vec<Mutation>) -> Step {
+        self.checkpoint(&population);
+        let mutated = self.mutate(population);
+        self.iterations_count += 1;
+        let validation_error = self.validate(&mutated);
+        self.observe(&mutated, validation_error);
+        if self.terminate(&mutated) {
+            return Step::Done(mutated);
+        }
+        let propagated = self.propagate(mutated);
+        Step::Continue(self.select(propagated))
+    }
+
+    pub fn emigrants(&self, population: &Vec<Mutation>, count: usize) -> Vec<Mutator> {
+        let errors = self.fitness(population);
+        (0..population.len())
+            .sorted_by(|&l, &r| errors[l].partial_cmp(&errors[r]).unwrap())
+            .into_iter()
+            .take(count)
+            .map(|x| population[x].mutator.clone())
+            .collect()
+    }
+
+    pub fn immigrate(&mut self, mut population: Vec<Mutation>, migrants: Vec<Mutator>) -> Vec<Mutation> {
+        let errors = self.fitness(&population);
+        let worst = (0..population.len())
+            .sorted_by(|&l, &r| errors[r].partial_cmp(&errors[l]).unwrap())
+            .into_iter()
+            .collect::<Vec<_>>();
+        for (index, mutator) in worst.into_iter().zip(migrants.into_iter()) {
+            self.innovations.register_mutator(&mutator);
+            let error = self.error(&mutator);
+            population[index] = Mutation {mutator: mutator, error: error};
+        }
+        population
+    }
+
+    pub fn error(&self, mutator: &Mutator) -> Value {
+        mutator.as_network_buf().as_network().error(self.conf.train_conf.error_conf)
+    }
+
+    pub fn node_id(&self) -> &IdGenerator {
+        self.conf.node_id
+    }
+
+    pub fn swap_node_id(&mut self, node_id: &mut IdGenerator) {
+        ::std::mem::swap(self.conf.node_id, node_id);
+    }
+
     fn mutate(&mut self, population: Vec<Mutation>) -> Vec<Mutation> {
         use self::rayon::prelude::{IntoParallelIterator, ParallelIterator, ExactParallelIterator};
-        let ref mut node_id = self.conf.node_id;
-        let ref mut rng = self.conf.rng;
+        let operations_count = self.conf.mutation_conf.operations_count;
+        let tasks = {
+            let ref mut node_id = self.conf.node_id;
+            let ref mut rng = self.conf.rng;
+            population.into_iter()
+                .map(|x| (x.mutator, rng.gen::<[u32; 4]>(), node_id.reserve(operations_count)))
+                .collect::<Vec<_>>()
+        };
+        let ref innovations = self.innovations;
         let ref train_conf = self.conf.train_conf;
-        let mut result = Vec::new();
-        population.into_iter()
-            .map(|x| x.mutator)
-            .map(|mut x| {
-                for _ in 0..3 {
-                    match rng.gen_range(0, 3) {
-                        0 => {
-                            let arc = x.random_arc(rng);
-                            x.split(node_id, &arc);
-                        },
-                        1 => loop {
-                            let src = x.random_node(rng);
-                            let dst = x.random_node(rng);
-                            if !x.graph().arcs().contains_key(&Arc(src, dst)) {
-                                x.add_arc(src, dst, 1e-3);
-                                break;
-                            }
-                        },
-                        2 => {
-                            let arc = x.random_arc(rng);
-                            x.rm_arc(&arc);
-                            x.rm_useless();
-                        },
-                        _ => (),
-                    }
-                }
-                x
-            })
-            .collect::<Vec<_>>()
-            .into_par_iter()
-            .map(|x| {
-                let mut network_buf = x.as_network_buf();
+        let mutation_conf = self.conf.mutation_conf;
+        let mut mutated = Vec::new();
+        tasks.into_par_iter()
+            .map(|(mut mutator, rng_seed, mut node_id)| {
+                let mut rng = RngT::from_seed(rng_seed);
+                let mut new_innovations = innovations.clone();
+                mutation_conf.mutate(&mut mutator, &mut node_id, &mut new_innovations, &mut rng);
+                let mut network_buf = mutator.as_network_buf();
                 let error = network_buf.as_network_mut().train(train_conf);
-                Mutation {
+                let mutation = Mutation {
                     mutator: Mutator::from_network(&network_buf.as_network()),
                     error: error,
-                }
+                };
+                (mutation, new_innovations)
             })
-            .collect_into(&mut result);
-        result
+            .collect_into(&mut mutated);
+        mutated.into_iter()
+            .map(|(mut mutation, new_innovations)| {
+                self.innovations.resolve(&new_innovations, &mut mutation.mutator);
+                mutation
+            })
+            .collect()
+    }
+
+    fn checkpoint(&mut self, population: &Vec<Mutation>) {
+        if self.conf.checkpoint_interval == 0
+            || self.iterations_count % self.conf.checkpoint_interval != 0
+            || self.checkpoint_iterations_count == Some(self.iterations_count) {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            population: population.iter().map(|x| Individual::new(&x.mutator, x.error)).collect(),
+            iterations_count: self.iterations_count,
+            node_id: self.conf.node_id.clone(),
+            rng_state: self.conf.rng.state(),
+            best: self.best.as_ref().map(|x| Individual::new(&x.mutator, x.error)),
+            stagnant_iterations_count: self.stagnant_iterations_count,
+            innovations: self.innovations.clone(),
+            species: self.speciation.species().iter()
+                .map(|x| (Individual::new(&x.representative, x.best_error), x.stagnant_iterations_count))
+                .collect(),
+            wall_time: self.wall_time(),
+        };
+        if let Some(ref mut writer) = self.conf.checkpoint_writer {
+            writer.write(&checkpoint);
+        }
+        self.checkpoint_iterations_count = Some(self.iterations_count);
+    }
+
+    fn validate(&mut self, population: &Vec<Mutation>) -> Option<Value> {
+        use self::rayon::prelude::{IntoParallelRefIterator, ParallelIterator, ExactParallelIterator};
+        let validation_error_conf = match self.conf.validation_error_conf {
+            Some(v) => v,
+            None => return None,
+        };
+        let mut errors = Vec::new();
+        population.par_iter()
+            .map(|x| x.mutator.as_network_buf().as_network().error(validation_error_conf))
+            .collect_into(&mut errors);
+        let (index, error) = errors.into_iter()
+            .enumerate()
+            .sorted_by(|l, r| l.1.partial_cmp(&r.1).unwrap())
+            .into_iter()
+            .nth(0).unwrap();
+        if self.best.as_ref().map_or(true, |x| error < x.error) {
+            self.best = Some(Mutation {mutator: population[index].mutator.clone(), error: error});
+            self.stagnant_iterations_count = 0;
+        } else {
+            self.stagnant_iterations_count += 1;
+        }
+        Some(error)
+    }
+
+    fn observe(&mut self, population: &Vec<Mutation>, validation_error: Option<Value>) {
+        use std::collections::HashSet;
+        if self.conf.observer.is_none() {
+            return;
+        }
+        let count = population.len() as Value;
+        let best = population.iter()
+            .sorted_by(|l, r| l.error.partial_cmp(&r.error).unwrap())
+            .into_iter()
+            .nth(0).unwrap();
+        let arcs = population.iter()
+            .map(|x| x.mutator.arcs().cloned().collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let mut distances_sum = 0.0;
+        let mut pairs_count = 0;
+        for (i, l) in arcs.iter().enumerate() {
+            for r in arcs[i + 1..].iter() {
+                let union_len = l.union(r).count();
+                if union_len > 0 {
+                    distances_sum += 1.0 - l.intersection(r).count() as Value / union_len as Value;
+                }
+                pairs_count += 1;
+            }
+        }
+        let stats = Stats {
+            iteration: self.iterations_count,
+            best_error: best.error,
+            mean_error: population.iter().map(|x| x.error).sum::<Value>() / count,
+            worst_error: population.iter().map(|x| x.error).fold(best.error, Value::max),
+            validation_error: validation_error,
+            mean_nodes_count: population.iter().map(|x| x.mutator.nodes().len()).sum::<usize>() as Value / count,
+            mean_arcs_count: population.iter().map(|x| x.mutator.arcs().len()).sum::<usize>() as Value / count,
+            best_nodes_count: best.mutator.nodes().len(),
+            best_arcs_count: best.mutator.arcs().len(),
+            diversity: if pairs_count > 0 { distances_sum / pairs_count as Value } else { 0.0 },
+            species_count: self.speciation.species().len(),
+            wall_time: self.wall_time(),
+        };
+        if let Some(ref mut observer) = self.conf.observer {
+            observer.observe(&stats);
+        }
+    }
+
+    fn wall_time(&self) -> Value {
+        let elapsed = self.started.elapsed();
+        self.elapsed_before + elapsed.as_secs() as Value + elapsed.subsec_nanos() as Value * 1e-9
     }
 
     fn begin(&self) -> bool {
@@ -156,6 +414,7 @@ impl<'c, 'f, RngT: 'c + Rng> Evolution<'c, 'f, RngT> {
         writeln!(stderr(), "Evolve {}/{} iterations done",
                self.iterations_count, self.conf.iterations_count).unwrap();
         self.iterations_count >= self.conf.iterations_count
+        || self.conf.patience.map_or(false, |x| self.stagnant_iterations_count >= x)
         || population.iter()
             .filter(|x| x.error <= self.conf.error)
             .count() > 0
@@ -167,11 +426,15 @@ impl<'c, 'f, RngT: 'c + Rng> Evolution<'c, 'f, RngT> {
             let mut b = population.iter().collect::<Vec<_>>();
             self.conf.rng.shuffle(&mut a[..]);
             self.conf.rng.shuffle(&mut b[..]);
+            let crossover = self.conf.crossover;
+            let error_conf = self.conf.train_conf.error_conf;
+            let ref mut rng = self.conf.rng;
             a.iter()
                 .zip(b.iter())
                 .map(|(l, r)| {
-                    let mutator = l.mutator.union(&r.mutator);
-                    let error = mutator.as_network_buf().as_network().error(self.conf.train_conf.error_conf);
+                    let (fitter, other) = if r.error < l.error { (r, l) } else { (l, r) };
+                    let mutator = crossover.cross(&fitter.mutator, &other.mutator, &mut **rng);
+                    let error = mutator.as_network_buf().as_network().error(error_conf);
                     Mutation {mutator: mutator, error: error}
                 })
                 .collect::<Vec<_>>()
@@ -180,31 +443,77 @@ impl<'c, 'f, RngT: 'c + Rng> Evolution<'c, 'f, RngT> {
         new
     }
 
-    fn select(&self, population: Vec<Mutation>) -> Vec<Mutation> {
-        population.into_iter()
-            .sorted_by(|l, r| l.error.partial_cmp(&r.error).unwrap())
+    fn select(&mut self, population: Vec<Mutation>) -> Vec<Mutation> {
+        let errors = self.fitness(&population);
+        let elite = (0..errors.len())
+            .sorted_by(|&l, &r| errors[l].partial_cmp(&errors[r]).unwrap())
+            .into_iter()
+            .take(self.conf.elitism_count.min(self.conf.population_size))
+            .collect::<Vec<_>>();
+        let pool = (0..errors.len())
+            .filter(|x| !elite.contains(x))
+            .collect::<Vec<_>>();
+        let count = self.conf.population_size - elite.len();
+        let (candidates, candidates_errors) = self.speciate(&population, &errors, pool, count);
+        let rest = self.conf.selection
+            .select(&candidates_errors, count, &mut *self.conf.rng)
             .into_iter()
-            .take(self.conf.population_size)
+            .map(|x| candidates[x])
+            .collect::<Vec<_>>();
+        elite.into_iter()
+            .chain(rest.into_iter())
+            .map(|x| population[x].clone())
             .collect()
     }
 
+    fn speciate(&mut self, population: &Vec<Mutation>, errors: &Vec<Value>, pool: Vec<usize>,
+                count: usize) -> (Vec<usize>, Vec<Value>) {
+        let pool_errors = pool.iter().map(|&x| errors[x]).collect::<Vec<_>>();
+        let speciation_conf = match self.conf.speciation_conf {
+            Some(v) => v,
+            None => return (pool, pool_errors),
+        };
+        let pairs = pool.iter()
+            .map(|&x| (&population[x].mutator, population[x].error))
+            .collect::<Vec<_>>();
+        let membership = self.speciation.assign(speciation_conf, &self.innovations, &pairs);
+        let shared = self.speciation.shared_errors(&membership, &pool_errors);
+        let stagnant = self.speciation.stagnant(speciation_conf);
+        let mut candidates = (0..pool.len())
+            .filter(|&x| !stagnant[membership[x]])
+            .collect::<Vec<_>>();
+        if candidates.len() < count {
+            candidates = (0..pool.len()).collect();
+        }
+        let candidates_errors = candidates.iter().map(|&x| shared[x]).collect();
+        (candidates.into_iter().map(|x| pool[x]).collect(), candidates_errors)
+    }
+
     fn select_one(&self, population: Vec<Mutation>) -> Mutator {
+        let errors = self.fitness(&population);
         population.into_iter()
-            .sorted_by(|l, r| l.error.partial_cmp(&r.error).unwrap())
+            .zip(errors.into_iter())
+            .sorted_by(|l, r| (l.1, l.0.error).partial_cmp(&(r.1, r.0.error)).unwrap())
             .into_iter()
-            .nth(0).unwrap().mutator
+            .nth(0).unwrap().0.mutator
+    }
+
+    fn fitness(&self, population: &Vec<Mutation>) -> Vec<Value> {
+        let pairs = population.iter().map(|x| (&x.mutator, x.error)).collect::<Vec<_>>();
+        self.conf.fitness.fitness(&pairs)
     }
 }
 
-#[test]
-fn test_evolve_should_succeed() {
-    extern crate rand;
+#[cfg(test)]
+fn with_test_train_conf<F>(f: F) where F: FnOnce(&Mutator, &train::Conf) {
     use std::collections::{BTreeSet, HashMap, HashSet};
-    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::activation::ActivationFunction;
     use neural_network::apply::{Conf as ApplyConf};
-    use neural_network::error::{Conf as ErrorConf, Sample};
-    use neural_network::train::{Conf as TrainConf};
+    use neural_network::common::Node;
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    use neural_network::train::{Bounds, Conf as TrainConf, Method};
     use neural_network::matrix::Matrix;
+    use neural_network::sparse_matrix::SparseMatrixBuf;
     use neural_network::network::Network;
     let mut weights_values = [
         0.0, 0.0, 0.0, 0.1, 0.1,
@@ -215,18 +524,25 @@ fn test_evolve_should_succeed() {
     ];
     let inputs = [0, 1, 2].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [3, 4].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(5, &mut weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(5, &mut weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..5).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 5];
+    let biases = vec![0.0; 5];
     let network = Network {
         inputs: &inputs,
         outputs: &outputs,
         weights: weights,
-        nodes: &nodes
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
     };
     let mutator = Mutator::from_network(&network);
     let apply_conf = ApplyConf {
         group_size: 1000,
         threshold: 1e-4,
+        mode: None,
+        max_visits: Some(10000),
     };
     let samples = [
         Sample {input: &[0.6, 0.7, 0.8], output: &[0.5, 0.4]},
@@ -235,101 +551,581 @@ fn test_evolve_should_succeed() {
     let error_conf = ErrorConf {
         apply_conf: &apply_conf,
         samples: &samples,
+        metric: &MetricFunction::Euclidean,
+        custom_activations: &HashMap::new(),
     };
     let train_conf = TrainConf {
         error_conf: &error_conf,
         max_function_calls_count: 111,
+        method: Method::Bobyqa,
+        allow_new_arcs: false,
+        weight_bounds: Bounds::default(),
+        bias_bounds: Bounds::symmetric(),
+        arc_bounds: HashMap::new(),
     };
-    let mut rng = XorShiftRng::new_unseeded();
-    rng.reseed([1, 1, 1, 1]);
-    let mut node_id = IdGenerator::new(0);
-    let mut conf = Conf {
-        train_conf: &train_conf,
-        rng: &mut rng,
-        node_id: &mut node_id,
-        population_size: 2,
+    f(&mutator, &train_conf)
+}
+
+#[cfg(test)]
+fn test_conf<'r, RngT>(train_conf: &'r train::Conf<'r>, mutation_conf: &'r mutation::Conf,
+                       rng: &'r mut RngT, node_id: &'r mut IdGenerator) -> Conf<'r, RngT>
+        where RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState {
+    use neural_network::crossover::CrossoverFunction;
+    use neural_network::fitness::FitnessFunction;
+    use neural_network::selection::SelectionFunction;
+    Conf {
+        train_conf: train_conf,
+        mutation_conf: mutation_conf,
+        selection: &SelectionFunction::Truncation,
+        crossover: &CrossoverFunction::Union,
+        elitism_count: 0,
+        fitness: &FitnessFunction::Error,
+        rng: rng,
+        node_id: node_id,
+        population_size: 4,
         error: 1e-3,
         iterations_count: 2,
-    };
-    let evolved = mutator.evolve(&mut conf);
-    let evolved_network_buf = evolved.as_network_buf();
-    let evolved_network = evolved_network_buf.as_network();
-    let result: &[f64] = evolved_network.weights.values();
-    assert_eq!(result, &[
-        0.0, 0.0, 0.0, 0.2683965623209851  , 0.17777079087862993   , 0.3539603853353258, 0.0,
-        0.0, 0.0, 0.0, 0.0                 , 0.6691012007499241    , 0.0               , 0.5829220793286896,
-        0.0, 0.0, 0.0, 0.47249060773832235 , 0.3731956371044444    , 0.0               , 0.0,
-        0.0, 0.0, 0.0, 0.010525576601664234, 0.00012966522945039157, 0.0               , 0.0,
-        0.0, 0.0, 0.0, 0.0                 , 0.02282920151502366   , 0.0               , 0.027966325746754388,
-        0.0, 0.0, 0.0, 0.0                 , 0.3304639663408785    , 0.0               , 0.0,
-        0.0, 0.0, 0.0, 0.5829220793286896  , 0.0                   , 0.0               , 0.0,
-    ] as &[f64]);
+        validation_error_conf: None,
+        patience: None,
+        checkpoint_interval: 0,
+        checkpoint_writer: None,
+        resume_from: None,
+        observer: None,
+        speciation_conf: None,
+    }
+}
+
+#[test]
+fn test_select_one_with_pareto_fitness_should_prefer_lower_error_in_first_front() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::fitness::{Complexity, FitnessFunction};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(100);
+        let fitness = FitnessFunction::Pareto(Complexity::default());
+        let mut larger = mutator.clone();
+        let arc = larger.arcs().next().cloned().unwrap();
+        larger.split(&mut IdGenerator::new(100), &arc);
+        let mut conf = Conf {
+            fitness: &fitness,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let evolution = Evolution::new(&mut conf, mutator);
+        let population = vec![
+            Mutation {mutator: mutator.clone(), error: 0.5},
+            Mutation {mutator: larger.clone(), error: 0.1},
+        ];
+        assert_eq!(evolution.fitness(&population), vec![0.0, 0.0]);
+        assert_eq!(evolution.select_one(population), larger);
+    });
+}
+
+#[test]
+fn test_evolve_front_with_pareto_fitness_should_keep_simpler_mutators_than_error_fitness() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::fitness::{Complexity, FitnessFunction};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let complexity = Complexity::default();
+        // Both runs need enough generations for complexity pressure to show against training noise.
+        let by_error = {
+            let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                elitism_count: 1,
+                population_size: 6,
+                iterations_count: 8,
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf)
+        };
+        let fitness = FitnessFunction::Pareto(complexity.clone());
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = Conf {
+            fitness: &fitness,
+            elitism_count: 1,
+            population_size: 6,
+            iterations_count: 8,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let front = mutator.evolve_front(&mut conf);
+        let simplest = front.iter().map(|x| complexity.measure(x)).fold(::std::f64::INFINITY, Value::min);
+        assert!(simplest < complexity.measure(&by_error));
+    });
+}
+
+#[test]
+fn test_evolve_front_with_nsga2_should_return_mutually_non_dominated_mutators() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::fitness::{Complexity, FitnessFunction, Objective, ObjectiveFunction};
+    use neural_network::mutation::{Conf as MutationConf};
+    use neural_network::pareto::dominates;
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let objectives = vec![
+            ObjectiveFunction::Error,
+            ObjectiveFunction::Complexity(Complexity {nodes: 1.0, arcs: 0.0, weights: 0.0}),
+            ObjectiveFunction::Complexity(Complexity {nodes: 0.0, arcs: 1.0, weights: 0.0}),
+        ];
+        let fitness = FitnessFunction::Nsga2(objectives.clone());
+        let mut conf = Conf {
+            fitness: &fitness,
+            elitism_count: 1,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let front = mutator.evolve_front(&mut conf);
+        let values = front.iter()
+            .map(|x| {
+                let error = x.as_network_buf().as_network().error(train_conf.error_conf);
+                objectives.iter().map(|objective| objective.objective(x, error)).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(!front.is_empty());
+        for (n, x) in front.iter().enumerate() {
+            assert!(front[n + 1..].iter().all(|y| x != y));
+            assert!(values.iter().all(|y| !dominates(y, &values[n])));
+        }
+    });
+}
+
+#[test]
+fn test_evolve_front_with_validation_should_order_mutators_by_validation_error() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    use neural_network::fitness::{Complexity, FitnessFunction, ObjectiveFunction};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let validation_samples = [
+            Sample {input: &[0.5, 0.6, 0.7], output: &[0.1, 0.1]},
+        ];
+        let validation_error_conf = ErrorConf {
+            apply_conf: train_conf.error_conf.apply_conf,
+            samples: &validation_samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: train_conf.error_conf.custom_activations,
+        };
+        let mut rng = XorShiftRng::from_seed([2, 2, 2, 2]);
+        let mut node_id = IdGenerator::new(0);
+        let fitness = FitnessFunction::Nsga2(vec![
+            ObjectiveFunction::Error,
+            ObjectiveFunction::Complexity(Complexity {nodes: 1.0, arcs: 1.0, weights: 0.0}),
+        ]);
+        let mut conf = Conf {
+            fitness: &fitness,
+            elitism_count: 1,
+            population_size: 6,
+            iterations_count: 6,
+            validation_error_conf: Some(&validation_error_conf),
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let front = mutator.evolve_front(&mut conf);
+        let errors = front.iter()
+            .map(|x| x.as_network_buf().as_network().error(&validation_error_conf))
+            .collect::<Vec<_>>();
+        let train_errors = front.iter()
+            .map(|x| x.as_network_buf().as_network().error(train_conf.error_conf))
+            .collect::<Vec<_>>();
+        assert!(front.len() > 1);
+        assert!(errors.windows(2).all(|x| x[0] <= x[1]));
+        assert!(train_errors[0] > train_errors[1]);
+    });
+}
+
+#[test]
+fn test_evolve_with_speciation_should_report_species() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::crossover::CrossoverFunction;
+    use neural_network::mutation::{Conf as MutationConf};
+    use neural_network::speciation::{Conf as SpeciationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let speciation_conf = SpeciationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut stats = Vec::new();
+        let mut observe = |x: &Stats| stats.push(x.clone());
+        {
+            let mut conf = Conf {
+                crossover: &CrossoverFunction::Aligned,
+                iterations_count: 3,
+                observer: Some(&mut observe),
+                speciation_conf: Some(&speciation_conf),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf);
+        }
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].species_count, 0);
+        for x in stats[1..].iter() {
+            assert!(0 < x.species_count && x.species_count <= 2 * 4);
+        }
+    });
+}
+
+#[test]
+fn test_evolve_should_succeed() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = Conf {
+            population_size: 2,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let initial_error = mutator.as_network_buf().as_network().error(train_conf.error_conf);
+        let evolved = mutator.evolve(&mut conf);
+        let evolved_error = evolved.as_network_buf().as_network().error(train_conf.error_conf);
+        assert!(evolved_error < initial_error);
+    });
 }
 
 #[test]
 fn test_evolve_zero_iterations_should_do_nothing() {
     extern crate rand;
-    use std::collections::{BTreeSet, HashMap, HashSet};
     use self::rand::{XorShiftRng, SeedableRng};
-    use neural_network::apply::{Conf as ApplyConf};
-    use neural_network::error::{Conf as ErrorConf, Sample};
-    use neural_network::train::{Conf as TrainConf};
-    use neural_network::matrix::Matrix;
-    use neural_network::network::Network;
-    let mut weights_values = [
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0,
-    ];
-    let inputs = [0, 1, 2].iter().cloned().collect::<BTreeSet<usize>>();
-    let outputs = [3, 4].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(5, &mut weights_values);
-    let nodes = (0..5).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
-    let network = Network {
-        inputs: &inputs,
-        outputs: &outputs,
-        weights: weights,
-        nodes: &nodes
-    };
-    let mutator = Mutator::from_network(&network);
-    let apply_conf = ApplyConf {
-        group_size: 1000,
-        threshold: 1e-4,
-    };
-    let samples = [
-        Sample {input: &[0.6, 0.7, 0.8], output: &[0.5, 0.4]},
-        Sample {input: &[0.3, 0.4, 0.5], output: &[0.3, 0.4]},
-    ];
-    let error_conf = ErrorConf {
-        apply_conf: &apply_conf,
-        samples: &samples,
-    };
-    let train_conf = TrainConf {
-        error_conf: &error_conf,
-        max_function_calls_count: 111,
-    };
-    let mut rng = XorShiftRng::new_unseeded();
-    rng.reseed([1, 1, 1, 1]);
-    let mut node_id = IdGenerator::new(0);
-    let mut conf = Conf {
-        train_conf: &train_conf,
-        rng: &mut rng,
-        node_id: &mut node_id,
-        population_size: 2,
-        error: 1e-3,
-        iterations_count: 0,
-    };
-    let evolved = mutator.evolve(&mut conf);
-    let evolved_network_buf = evolved.as_network_buf();
-    let evolved_network = evolved_network_buf.as_network();
-    let result: &[f64] = evolved_network.weights.values();
-    assert_eq!(result, &[
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.1, 0.1,
-        0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0,
-    ] as &[f64]);
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = Conf {
+            population_size: 2,
+            iterations_count: 0,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let evolved = mutator.evolve(&mut conf);
+        let evolved_network_buf = evolved.as_network_buf();
+        let evolved_weights = evolved_network_buf.as_network().weights.to_matrix_buf(0.0);
+        let evolved_matrix = evolved_weights.as_matrix();
+        let result: &[f64] = evolved_matrix.values();
+        assert_eq!(result, &[
+            0.0, 0.0, 0.0, 0.1, 0.1,
+            0.0, 0.0, 0.0, 0.1, 0.1,
+            0.0, 0.0, 0.0, 0.1, 0.1,
+            0.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 0.0,
+        ] as &[f64]);
+    });
+}
+
+#[test]
+fn test_evolve_with_validation_should_stop_early_and_return_best_validated_mutator() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let validation_samples = [
+            Sample {input: &[0.5, 0.6, 0.7], output: &[0.45, 0.4]},
+        ];
+        let validation_error_conf = ErrorConf {
+            apply_conf: train_conf.error_conf.apply_conf,
+            samples: &validation_samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: train_conf.error_conf.custom_activations,
+        };
+        let mut rng = XorShiftRng::from_seed([2, 2, 2, 2]);
+        let mut node_id = IdGenerator::new(0);
+        let mut stats = Vec::new();
+        let evolved = {
+            let mut observe = |x: &Stats| stats.push(x.clone());
+            let mut conf = Conf {
+                population_size: 2,
+                iterations_count: 100,
+                validation_error_conf: Some(&validation_error_conf),
+                patience: Some(1),
+                observer: Some(&mut observe),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf)
+        };
+        let initial_error = mutator.as_network_buf().as_network().error(&validation_error_conf);
+        let evolved_error = evolved.as_network_buf().as_network().error(&validation_error_conf);
+        assert!(stats.len() < 100);
+        assert!(stats.iter().all(|x| x.best_error > 1e-3));
+        let best_validation_error = stats.iter()
+            .map(|x| x.validation_error.unwrap())
+            .fold(initial_error, Value::min);
+        assert_eq!(evolved_error, best_validation_error);
+        let last = stats.last().unwrap();
+        assert!(stats.iter().all(|x| last.best_error <= x.best_error));
+        assert!(evolved_error < last.validation_error.unwrap());
+    });
+}
+
+#[test]
+fn test_evolve_resumed_from_checkpoint_should_give_same_result() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut checkpoints = Vec::new();
+        let evolved = {
+            let mut write_checkpoint = |checkpoint: &Checkpoint| checkpoints.push(checkpoint.clone());
+            let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                population_size: 2,
+                iterations_count: 3,
+                checkpoint_interval: 1,
+                checkpoint_writer: Some(&mut write_checkpoint),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf)
+        };
+        assert!(checkpoints.len() > 1);
+        let mut rng = XorShiftRng::from_seed([2, 2, 2, 2]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = Conf {
+            population_size: 2,
+            iterations_count: 3,
+            checkpoint_interval: 1,
+            resume_from: Some(&checkpoints[1]),
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        assert_eq!(mutator.evolve(&mut conf), evolved);
+    });
+}
+
+#[test]
+fn test_evolve_resumed_from_checkpoint_should_continue_wall_time() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut checkpoints = Vec::new();
+        {
+            let mut write_checkpoint = |checkpoint: &Checkpoint| checkpoints.push(checkpoint.clone());
+            let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                checkpoint_interval: 1,
+                checkpoint_writer: Some(&mut write_checkpoint),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf);
+        }
+        assert!(checkpoints.len() > 1);
+        assert!(checkpoints.windows(2).all(|x| x[0].wall_time <= x[1].wall_time));
+        let mut checkpoint = checkpoints[1].clone();
+        checkpoint.wall_time = 1000.0;
+        let mut stats = Vec::new();
+        {
+            let mut observe = |x: &Stats| stats.push(x.clone());
+            let mut rng = XorShiftRng::from_seed([2, 2, 2, 2]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                resume_from: Some(&checkpoint),
+                observer: Some(&mut observe),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf);
+        }
+        assert!(!stats.is_empty());
+        assert!(stats.iter().all(|x| x.wall_time >= 1000.0));
+    });
+}
+
+#[test]
+fn test_evolve_should_report_stats_for_each_generation() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut stats = Vec::new();
+        {
+            let mut observe = |x: &Stats| stats.push(x.clone());
+            let mut conf = Conf {
+                population_size: 2,
+                iterations_count: 3,
+                observer: Some(&mut observe),
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf);
+        }
+        assert!(!stats.is_empty());
+        for (n, x) in stats.iter().enumerate() {
+            assert_eq!(x.iteration, n + 1);
+            assert!(x.best_error <= x.mean_error && x.mean_error <= x.worst_error);
+            assert!(x.best_nodes_count >= 5 && x.best_arcs_count > 0);
+            assert!(0.0 <= x.diversity && x.diversity <= 1.0);
+            assert_eq!(x.validation_error, None);
+            assert_eq!(x.species_count, 0);
+        }
+    });
+}
+
+#[test]
+fn test_select_with_elitism_should_not_duplicate_elites() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    use neural_network::selection::SelectionFunction;
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let arc = mutator.arcs().next().cloned().unwrap();
+        let population = [0.4, 0.1, 0.3, 0.2].iter()
+            .map(|&error| {
+                let mut mutator = mutator.clone();
+                mutator.set_weight(&arc, error);
+                Mutation {mutator: mutator, error: error}
+            })
+            .collect::<Vec<_>>();
+        for selection in [SelectionFunction::Truncation, SelectionFunction::Tournament(4)].iter() {
+            let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                selection: selection,
+                elitism_count: 1,
+                population_size: 2,
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            let mut evolution = Evolution::new(&mut conf, mutator);
+            let selected = evolution.select(population.clone());
+            assert_eq!(selected.len(), 2);
+            assert_eq!(selected[0].error, 0.1);
+            assert!(selected[1].mutator != selected[0].mutator);
+        }
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = Conf {
+            elitism_count: 1,
+            population_size: 2,
+            ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+        };
+        let mut evolution = Evolution::new(&mut conf, mutator);
+        let errors = evolution.select(population.clone()).iter().map(|x| x.error).collect::<Vec<_>>();
+        assert_eq!(errors, vec![0.1, 0.2]);
+    });
+}
+
+#[test]
+fn test_immigrate_should_replace_worst_mutations_with_migrants() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let arc = mutator.arcs().next().cloned().unwrap();
+        let population = [0.1, 0.3, 0.2].iter()
+            .map(|&error| {
+                let mut mutator = mutator.clone();
+                mutator.set_weight(&arc, error);
+                Mutation {mutator: mutator, error: error}
+            })
+            .collect::<Vec<_>>();
+        let mut migrant = mutator.clone();
+        migrant.rm_arc(&arc);
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(0);
+        let mut conf = test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id);
+        let mut evolution = Evolution::new(&mut conf, mutator);
+        let immigrated = evolution.immigrate(population.clone(), vec![migrant.clone()]);
+        assert!(immigrated[0].mutator == population[0].mutator);
+        assert!(immigrated[1].mutator == migrant);
+        assert_eq!(immigrated[1].error, evolution.error(&migrant));
+        assert!(immigrated[2].mutator == population[2].mutator);
+    });
+}
+
+#[test]
+fn test_evolve_should_give_same_result_for_any_checkpoint_interval() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let evolve = |checkpoint_interval: usize| {
+            let mut checkpoints_count = 0;
+            let mut write_checkpoint = |_: &Checkpoint| checkpoints_count += 1;
+            let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+            let mut node_id = IdGenerator::new(0);
+            let mut conf = Conf {
+                iterations_count: 3,
+                checkpoint_interval: checkpoint_interval,
+                checkpoint_writer: if checkpoint_interval > 1 { Some(&mut write_checkpoint) } else { None },
+                ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+            };
+            mutator.evolve(&mut conf)
+        };
+        let expected = evolve(0);
+        assert_eq!(evolve(1), expected);
+        assert_eq!(evolve(2), expected);
+    });
+}
+
+#[test]
+fn test_mutate_same_split_in_one_generation_should_give_same_structure() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf {
+            operations_count: 1,
+            split_arc: 1.0,
+            add_arc: 0.0,
+            rm_arc: 0.0,
+            ..MutationConf::default()
+        };
+        let mut single_arc = mutator.clone();
+        for arc in mutator.arcs().skip(1) {
+            single_arc.rm_arc(arc);
+        }
+        let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+        let mut node_id = IdGenerator::new(5);
+        let mut conf = test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id);
+        let mut evolution = Evolution::new(&mut conf, &single_arc);
+        let population = evolution.initialize().unwrap();
+        let mutated = evolution.mutate(population);
+        assert_eq!(mutated[0].mutator.nodes().count(), 6);
+        for x in mutated[1..].iter() {
+            assert_eq!(x.mutator, mutated[0].mutator);
+        }
+    });
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_evolve_should_give_same_result_for_any_threads_count() {
+    extern crate rand;
+    use self::rand::{XorShiftRng, SeedableRng};
+    use self::rayon::{Configuration, ThreadPool};
+    use neural_network::mutation::{Conf as MutationConf};
+    with_test_train_conf(|mutator, train_conf| {
+        let mutation_conf = MutationConf::default();
+        let evolve = |threads_count: usize| {
+            let pool = ThreadPool::new(Configuration::new().num_threads(threads_count)).unwrap();
+            pool.install(|| {
+                let mut rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+                let mut node_id = IdGenerator::new(0);
+                let mut conf = Conf {
+                    iterations_count: 3,
+                    ..test_conf(train_conf, &mutation_conf, &mut rng, &mut node_id)
+                };
+                mutator.evolve(&mut conf).as_network_buf()
+            })
+        };
+        assert_eq!(evolve(1), evolve(4));
+    });
 }
@@ -0,0 +1,198 @@
+use super::common::{Value, compare};
+use super::mutator::Mutator;
+use super::pareto::{crowding_distances, non_dominated_sort};
+
+pub trait Fitness: Sync {
+    fn fitness(&self, population: &[(&Mutator, Value)]) -> Vec<Value>;
+
+    fn front(&self, population: &[(&Mutator, Value)]) -> Vec<usize> {
+        minimal(&self.fitness(population))
+    }
+}
+
+fn minimal(fitness: &[Value]) -> Vec<usize> {
+    let best = fitness.iter().cloned().fold(::std::f64::INFINITY, Value::min);
+    (0..fitness.len()).filter(|&x| fitness[x] == best).collect()
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Complexity {
+    pub nodes: Value,
+    pub arcs: Value,
+    pub weights: Value,
+}
+
+impl Default for Complexity {
+    fn default() -> Self {
+        Complexity {
+            nodes: 1.0,
+            arcs: 1.0,
+            weights: 0.0,
+        }
+    }
+}
+
+impl Complexity {
+    pub fn measure(&self, mutator: &Mutator) -> Value {
+        self.nodes * mutator.nodes().len() as Value
+        + self.arcs * mutator.arcs().len() as Value
+        + self.weights * mutator.graph().arcs().values().map(|x| x.abs()).sum::<Value>()
+    }
+}
+
+pub trait Objective: Sync {
+    fn objective(&self, mutator: &Mutator, error: Value) -> Value;
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum ObjectiveFunction {
+    Error,
+    Complexity(Complexity),
+}
+
+impl Objective for ObjectiveFunction {
+    fn objective(&self, mutator: &Mutator, error: Value) -> Value {
+        match *self {
+            ObjectiveFunction::Error => error,
+            ObjectiveFunction::Complexity(ref complexity) => complexity.measure(mutator),
+        }
+    }
+}
+
+impl<F> Objective for F where F: Fn(&Mutator, Value) -> Value + Sync {
+    fn objective(&self, mutator: &Mutator, error: Value) -> Value {
+        self(mutator, error)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum FitnessFunction {
+    Error,
+    Penalized(Complexity),
+    Pareto(Complexity),
+    Nsga2(Vec<ObjectiveFunction>),
+}
+
+impl Default for FitnessFunction {
+    fn default() -> Self {
+        FitnessFunction::Error
+    }
+}
+
+impl Fitness for FitnessFunction {
+    fn fitness(&self, population: &[(&Mutator, Value)]) -> Vec<Value> {
+        match *self {
+            FitnessFunction::Error => population.iter().map(|&(_, error)| error).collect(),
+            FitnessFunction::Penalized(ref complexity) => population.iter()
+                .map(|&(mutator, error)| error + complexity.measure(mutator))
+                .collect(),
+            FitnessFunction::Pareto(_) => {
+                let objectives = self.objectives(population);
+                let mut result = vec![0.0; population.len()];
+                for (rank, front) in non_dominated_sort(&objectives).into_iter().enumerate() {
+                    for x in front.into_iter() {
+                        result[x] = rank as Value;
+                    }
+                }
+                result
+            },
+            FitnessFunction::Nsga2(_) => {
+                let objectives = self.objectives(population);
+                let mut result = vec![0.0; population.len()];
+                for (rank, front) in non_dominated_sort(&objectives).into_iter().enumerate() {
+                    let distances = crowding_distances(&objectives, &front);
+                    let mut order = (0..front.len()).collect::<Vec<_>>();
+                    order.sort_by(|&l, &r| compare(&distances[r], &distances[l]));
+                    for (position, x) in order.into_iter().enumerate() {
+                        result[front[x]] = rank as Value + position as Value / front.len() as Value;
+                    }
+                }
+                result
+            },
+        }
+    }
+
+    fn front(&self, population: &[(&Mutator, Value)]) -> Vec<usize> {
+        match *self {
+            FitnessFunction::Pareto(_) | FitnessFunction::Nsga2(_) => {
+                let objectives = self.objectives(population);
+                non_dominated_sort(&objectives).into_iter().nth(0).unwrap_or_default()
+            },
+            _ => minimal(&self.fitness(population)),
+        }
+    }
+}
+
+impl FitnessFunction {
+    fn objectives(&self, population: &[(&Mutator, Value)]) -> Vec<Vec<Value>> {
+        let objectives = match *self {
+            FitnessFunction::Pareto(ref complexity) => vec![
+                ObjectiveFunction::Error,
+                ObjectiveFunction::Complexity(complexity.clone()),
+            ],
+            FitnessFunction::Nsga2(ref objectives) => objectives.clone(),
+            _ => vec![ObjectiveFunction::Error],
+        };
+        population.iter()
+            .map(|&(mutator, error)| objectives.iter().map(|x| x.objective(mutator, error)).collect())
+            .collect()
+    }
+}
+
+impl<F> Fitness for F where F: Fn(&Mutator, Value) -> Value + Sync {
+    fn fitness(&self, population: &[(&Mutator, Value)]) -> Vec<Value> {
+        population.iter().map(|&(mutator, error)| self(mutator, error)).collect()
+    }
+}
+
+#[test]
+fn test_penalized_should_add_complexity_to_error() {
+    use super::id_generator::IdGenerator;
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 2, 1, -0.5);
+    let fitness = FitnessFunction::Penalized(Complexity {nodes: 0.1, arcs: 0.01, weights: 1.0});
+    assert_eq!(fitness.fitness(&[(&mutator, 0.25)]), vec![0.25 + (0.1 * 3.0 + 0.01 * 2.0 + 1.0 * 1.0)]);
+}
+
+#[test]
+fn test_pareto_should_rank_by_fronts() {
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut node_id = IdGenerator::new(0);
+    let small = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let mut large = small.clone();
+    large.split(&mut node_id, &Arc(Node(0), Node(1)));
+    let fitness = FitnessFunction::Pareto(Complexity::default());
+    let population = [(&small, 0.3), (&large, 0.1), (&large, 0.4), (&small, 0.5)];
+    assert_eq!(fitness.fitness(&population), vec![0.0, 0.0, 1.0, 1.0]);
+}
+
+#[test]
+fn test_closure_should_be_usable_as_fitness() {
+    use super::id_generator::IdGenerator;
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let fitness = |mutator: &Mutator, error: Value| error * mutator.arcs().len() as Value;
+    assert_eq!(fitness.fitness(&[(&mutator, 0.5)]), vec![0.5]);
+}
+
+#[test]
+fn test_nsga2_should_order_front_by_crowding_distance() {
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut node_id = IdGenerator::new(0);
+    let small = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let mut medium = small.clone();
+    medium.split(&mut node_id, &Arc(Node(0), Node(1)));
+    let mut large = medium.clone();
+    large.split(&mut node_id, &Arc(Node(0), Node(2)));
+    let fitness = FitnessFunction::Nsga2(vec![
+        ObjectiveFunction::Error,
+        ObjectiveFunction::Complexity(Complexity::default()),
+    ]);
+    let population = [(&small, 0.3), (&medium, 0.2), (&large, 0.1), (&large, 0.4)];
+    assert_eq!(fitness.fitness(&population), vec![0.0, 2.0 / 3.0, 1.0 / 3.0, 1.0]);
+    assert_eq!(fitness.front(&population), vec![0, 1, 2]);
+}
@@ -3,7 +3,8 @@ extern crate dot;
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashSet};
 
-use super::common::{Node, Weight};
+use super::activation::ActivationFunction;
+use super::common::{Node, Value, Weight};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd)]
 pub struct Arc(pub Node, pub Node);
@@ -16,6 +17,8 @@ pub struct NodeArcs {
 
 pub type Nodes = BTreeMap<Node, NodeArcs>;
 pub type Arcs = BTreeMap<Arc, Weight>;
+pub type Activations = BTreeMap<Node, ActivationFunction>;
+pub type Biases = BTreeMap<Node, Value>;
 
 #[derive(Clone)]
 pub enum ArcsType {
@@ -44,6 +47,8 @@ impl ArcsType {
 pub struct Graph {
     nodes: Nodes,
     arcs: Arcs,
+    activations: Activations,
+    biases: Biases,
 }
 
 impl Graph {
@@ -51,6 +56,8 @@ impl Graph {
         Graph {
             nodes: Nodes::new(),
             arcs: Arcs::new(),
+            activations: Activations::new(),
+            biases: Biases::new(),
         }
     }
 
@@ -70,6 +77,40 @@ impl Graph {
         self.arcs[arc]
     }
 
+    pub fn activations(&self) -> &Activations {
+        &self.activations
+    }
+
+    pub fn node_activation(&self, node: &Node) -> ActivationFunction {
+        self.activations.get(node).cloned().unwrap_or_default()
+    }
+
+    pub fn set_activation(&mut self, node: Node, activation: ActivationFunction) {
+        assert!(self.nodes.contains_key(&node));
+        if activation == ActivationFunction::Identity {
+            self.activations.remove(&node);
+        } else {
+            self.activations.insert(node, activation);
+        }
+    }
+
+    pub fn biases(&self) -> &Biases {
+        &self.biases
+    }
+
+    pub fn node_bias(&self, node: &Node) -> Value {
+        self.biases.get(node).cloned().unwrap_or(0.0)
+    }
+
+    pub fn set_bias(&mut self, node: Node, bias: Value) {
+        assert!(self.nodes.contains_key(&node));
+        if bias == 0.0 {
+            self.biases.remove(&node);
+        } else {
+            self.biases.insert(node, bias);
+        }
+    }
+
     pub fn add_node(&mut self, id: Node) {
         assert!(!self.nodes.contains_key(&id));
         self.nodes.insert(id, NodeArcs {outgoing: HashSet::new(), incoming: HashSet::new()});
@@ -81,6 +122,8 @@ impl Graph {
             assert!(arcs.incoming.is_empty() && arcs.outgoing.is_empty());
         }
         self.nodes.remove(id);
+        self.activations.remove(id);
+        self.biases.remove(id);
     }
 
     pub fn add_arc(&mut self, src: Node, dst: Node, weight: Weight) -> Arc {
@@ -94,6 +137,10 @@ impl Graph {
         arc
     }
 
+    pub fn set_arc_weight(&mut self, arc: &Arc, weight: Weight) {
+        *self.arcs.get_mut(arc).unwrap() = weight;
+    }
+
     pub fn rm_arc(&mut self, arc: &Arc) {
         self.arcs.remove(arc);
         let &Arc(src, dst) = arc;
@@ -121,7 +168,16 @@ impl Graph {
             nodes.get_mut(&src).unwrap().outgoing.insert(arc.clone());
             nodes.get_mut(&dst).unwrap().incoming.insert(arc.clone());
         }
-        Graph {nodes: nodes, arcs: arcs}
+        let mut activations = other.activations.clone();
+        activations.extend(self.activations.clone());
+        let biases: Biases = nodes.keys()
+            .filter_map(|node| {
+                let graphs_count = self.nodes.contains_key(node) as usize
+                    + other.nodes.contains_key(node) as usize;
+                let bias = (self.node_bias(node) + other.node_bias(node)) / graphs_count as Value;
+                if bias == 0.0 { None } else { Some((*node, bias)) }
+            }).collect();
+        Graph {nodes: nodes, arcs: arcs, activations: activations, biases: biases}
     }
 
     pub fn unreachable_from<'r, Nodes>(&self, nodes: Nodes) -> HashSet<Node>
@@ -174,7 +230,14 @@ impl<'a> dot::Labeller<'a, Node, Arc> for Graph {
 
     fn node_label<'b>(&'b self, node: &Node) -> dot::LabelText<'b> {
         let &Node(id) = node;
-        dot::LabelText::LabelStr(Cow::Owned(format!("{}", id)))
+        let mut label = format!("{}", id);
+        if let Some(activation) = self.activations.get(node) {
+            label.push_str(&format!(" {:?}", activation));
+        }
+        if let Some(bias) = self.biases.get(node) {
+            label.push_str(&format!(" {:+}", bias));
+        }
+        dot::LabelText::LabelStr(Cow::Owned(label))
     }
 
     fn edge_label<'b>(&'b self, arc: &Arc) -> dot::LabelText<'b> {
@@ -323,6 +386,73 @@ fn test_union_should_succeed() {
     ].iter().cloned().collect::<Arcs>());
 }
 
+#[test]
+fn test_set_activation_should_succeed() {
+    let mut graph = Graph::new();
+    graph.add_node(Node(42));
+    graph.set_activation(Node(42), ActivationFunction::Tanh);
+    assert_eq!(graph.node_activation(&Node(42)), ActivationFunction::Tanh);
+    graph.set_activation(Node(42), ActivationFunction::Identity);
+    assert!(graph.activations().is_empty());
+}
+
+#[test]
+fn test_union_should_prefer_own_activation() {
+    let node = Node(1);
+    let mut graph1 = Graph::new();
+    graph1.add_node(node);
+    graph1.set_activation(node, ActivationFunction::Sigmoid);
+    let mut graph2 = Graph::new();
+    graph2.add_node(node);
+    graph2.add_node(Node(2));
+    graph2.set_activation(node, ActivationFunction::Relu);
+    graph2.set_activation(Node(2), ActivationFunction::Step);
+    let union = graph1.union(&graph2);
+    assert_eq!(union.node_activation(&node), ActivationFunction::Sigmoid);
+    assert_eq!(union.node_activation(&Node(2)), ActivationFunction::Step);
+}
+
+#[test]
+fn test_set_bias_should_succeed() {
+    let mut graph = Graph::new();
+    graph.add_node(Node(42));
+    graph.set_bias(Node(42), 0.3);
+    assert_eq!(graph.node_bias(&Node(42)), 0.3);
+    graph.set_bias(Node(42), 0.0);
+    assert!(graph.biases().is_empty());
+}
+
+#[test]
+fn test_union_should_average_biases() {
+    let node1 = Node(1);
+    let node2 = Node(2);
+    let mut graph1 = Graph::new();
+    graph1.add_node(node1);
+    graph1.set_bias(node1, 1.0);
+    let mut graph2 = Graph::new();
+    graph2.add_node(node1);
+    graph2.add_node(node2);
+    graph2.set_bias(node1, 0.5);
+    graph2.set_bias(node2, 0.2);
+    let union = graph1.union(&graph2);
+    assert_eq!(union.biases(), &[(node1, 0.75), (node2, 0.2)].iter().cloned().collect::<Biases>());
+}
+
+#[test]
+fn test_union_should_average_implicit_zero_bias_of_common_node() {
+    let node1 = Node(1);
+    let node2 = Node(2);
+    let mut graph1 = Graph::new();
+    graph1.add_node(node1);
+    graph1.add_node(node2);
+    graph1.set_bias(node1, 1.0);
+    let mut graph2 = Graph::new();
+    graph2.add_node(node1);
+    graph2.add_node(node2);
+    let union = graph1.union(&graph2);
+    assert_eq!(union.biases(), &[(node1, 0.5)].iter().cloned().collect::<Biases>());
+}
+
 #[test]
 fn test_connected_component_by_outgoing_for_src_with_arc_to_dst_should_return_both() {
     let mut graph = Graph::new();
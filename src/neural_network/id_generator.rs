@@ -1,4 +1,4 @@
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
 pub struct IdGenerator {
     next_id: usize,
 }
@@ -13,6 +13,16 @@ impl IdGenerator {
         self.next_id = current + 1;
         current
     }
+
+    pub fn merge(&mut self, other: &IdGenerator) {
+        self.next_id = self.next_id.max(other.next_id);
+    }
+
+    pub fn reserve(&mut self, count: usize) -> IdGenerator {
+        let block = IdGenerator {next_id: self.next_id};
+        self.next_id += count;
+        block
+    }
 }
 
 #[test]
@@ -24,3 +34,21 @@ fn test_new_should_succeed() {
 fn test_first_generated_should_be_equal_to_initial() {
     assert_eq!(IdGenerator::new(42).generate(), 42);
 }
+
+#[test]
+fn test_reserve_should_give_disjoint_block() {
+    let mut id_generator = IdGenerator::new(42);
+    let mut block = id_generator.reserve(2);
+    assert_eq!(block.generate(), 42);
+    assert_eq!(block.generate(), 43);
+    assert_eq!(id_generator.generate(), 44);
+}
+
+#[test]
+fn test_merge_should_not_generate_ids_given_by_other() {
+    let mut id_generator = IdGenerator::new(1);
+    id_generator.merge(&IdGenerator::new(42));
+    assert_eq!(id_generator.generate(), 42);
+    id_generator.merge(&IdGenerator::new(0));
+    assert_eq!(id_generator.generate(), 43);
+}
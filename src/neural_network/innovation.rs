@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+
+use super::common::Node;
+use super::id_generator::IdGenerator;
+use super::mutator::{Arc, Mutator};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Innovations {
+    next_number: usize,
+    arcs: BTreeMap<Arc, usize>,
+    splits: BTreeMap<Arc, Node>,
+}
+
+impl Innovations {
+    pub fn new() -> Innovations {
+        Innovations {
+            next_number: 0,
+            arcs: BTreeMap::new(),
+            splits: BTreeMap::new(),
+        }
+    }
+
+    pub fn number(&self, arc: &Arc) -> Option<usize> {
+        self.arcs.get(arc).cloned()
+    }
+
+    pub fn register(&mut self, arc: &Arc) -> usize {
+        if let Some(&number) = self.arcs.get(arc) {
+            return number;
+        }
+        let number = self.next_number;
+        self.next_number += 1;
+        self.arcs.insert(arc.clone(), number);
+        number
+    }
+
+    pub fn register_mutator(&mut self, mutator: &Mutator) {
+        for arc in mutator.arcs() {
+            self.register(arc);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Innovations) {
+        for (arc, &middle) in other.splits.iter() {
+            self.splits.entry(arc.clone()).or_insert(middle);
+        }
+        let mut arcs = other.arcs.iter().collect::<Vec<_>>();
+        arcs.sort_by_key(|&(_, &number)| number);
+        for (arc, _) in arcs.into_iter() {
+            self.register(arc);
+        }
+    }
+
+    pub fn resolve(&mut self, other: &Innovations, mutator: &mut Mutator) {
+        let mut splits = other.splits.iter()
+            .map(|(arc, &middle)| (middle, arc.clone()))
+            .collect::<Vec<_>>();
+        splits.sort();
+        let mut renamed = BTreeMap::new();
+        for (middle, Arc(src, dst)) in splits.into_iter() {
+            let arc = Arc(renamed.get(&src).cloned().unwrap_or(src), renamed.get(&dst).cloned().unwrap_or(dst));
+            let registered = *self.splits.entry(arc).or_insert(middle);
+            if registered != middle && mutator.graph().nodes().contains_key(&middle)
+                    && !mutator.graph().nodes().contains_key(&registered) {
+                mutator.rename_node(&middle, registered);
+                renamed.insert(middle, registered);
+            }
+        }
+        self.register_mutator(mutator);
+    }
+
+    pub fn split(&mut self, mutator: &mut Mutator, node_id: &mut IdGenerator, arc: &Arc) {
+        let registered = match self.splits.get(arc) {
+            Some(middle) if !mutator.graph().nodes().contains_key(middle) => Some(*middle),
+            _ => None,
+        };
+        let middle = match registered {
+            Some(middle) => middle,
+            None => {
+                let mut middle = Node(node_id.generate());
+                while mutator.graph().nodes().contains_key(&middle) {
+                    middle = Node(node_id.generate());
+                }
+                self.splits.entry(arc.clone()).or_insert(middle);
+                middle
+            },
+        };
+        mutator.split_at(middle, arc);
+        self.register_mutator(mutator);
+    }
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+struct InnovationsData {
+    next_number: usize,
+    arcs: Vec<(Node, Node, usize)>,
+    splits: Vec<(Node, Node, Node)>,
+}
+
+impl Encodable for Innovations {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        InnovationsData {
+            next_number: self.next_number,
+            arcs: self.arcs.iter().map(|(&Arc(src, dst), &number)| (src, dst, number)).collect(),
+            splits: self.splits.iter().map(|(&Arc(src, dst), &middle)| (src, dst, middle)).collect(),
+        }.encode(s)
+    }
+}
+
+impl Decodable for Innovations {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Innovations, D::Error> {
+        let data = InnovationsData::decode(d)?;
+        Ok(Innovations {
+            next_number: data.next_number,
+            arcs: data.arcs.into_iter().map(|(src, dst, number)| (Arc(src, dst), number)).collect(),
+            splits: data.splits.into_iter().map(|(src, dst, middle)| (Arc(src, dst), middle)).collect(),
+        })
+    }
+}
+
+#[test]
+fn test_register_should_assign_increasing_numbers_once() {
+    let mut innovations = Innovations::new();
+    assert_eq!(innovations.register(&Arc(Node(0), Node(1))), 0);
+    assert_eq!(innovations.register(&Arc(Node(1), Node(2))), 1);
+    assert_eq!(innovations.register(&Arc(Node(0), Node(1))), 0);
+    assert_eq!(innovations.number(&Arc(Node(2), Node(3))), None);
+}
+
+#[test]
+fn test_split_same_arc_in_different_mutators_should_give_same_structure() {
+    let mut node_id = IdGenerator::new(0);
+    let initial = Mutator::new(&mut node_id, 1, 1, 0.25);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&initial);
+    let mut first = initial.clone();
+    let mut second = initial.clone();
+    innovations.split(&mut first, &mut node_id, &Arc(Node(0), Node(1)));
+    innovations.split(&mut second, &mut node_id, &Arc(Node(0), Node(1)));
+    assert_eq!(first, second);
+    assert_eq!(innovations.number(&Arc(Node(0), Node(2))), Some(1));
+    assert_eq!(innovations.number(&Arc(Node(2), Node(1))), Some(2));
+}
+
+#[test]
+fn test_merge_should_keep_registered_and_add_new_in_order() {
+    let mut innovations = Innovations::new();
+    innovations.register(&Arc(Node(0), Node(1)));
+    let mut first = innovations.clone();
+    first.register(&Arc(Node(2), Node(1)));
+    first.register(&Arc(Node(0), Node(2)));
+    let mut second = innovations.clone();
+    second.register(&Arc(Node(3), Node(1)));
+    innovations.merge(&first);
+    innovations.merge(&second);
+    assert_eq!(innovations.number(&Arc(Node(0), Node(1))), Some(0));
+    assert_eq!(innovations.number(&Arc(Node(2), Node(1))), Some(1));
+    assert_eq!(innovations.number(&Arc(Node(0), Node(2))), Some(2));
+    assert_eq!(innovations.number(&Arc(Node(3), Node(1))), Some(3));
+}
+
+#[test]
+fn test_resolve_should_rename_middle_node_of_same_split() {
+    let mut node_id = IdGenerator::new(0);
+    let initial = Mutator::new(&mut node_id, 1, 1, 0.25);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&initial);
+    let mut first = initial.clone();
+    let mut first_innovations = innovations.clone();
+    first_innovations.split(&mut first, &mut IdGenerator::new(10), &Arc(Node(0), Node(1)));
+    first_innovations.split(&mut first, &mut IdGenerator::new(11), &Arc(Node(10), Node(1)));
+    let mut second = initial.clone();
+    let mut second_innovations = innovations.clone();
+    second_innovations.split(&mut second, &mut IdGenerator::new(20), &Arc(Node(0), Node(1)));
+    second_innovations.split(&mut second, &mut IdGenerator::new(21), &Arc(Node(20), Node(1)));
+    innovations.resolve(&first_innovations, &mut first);
+    innovations.resolve(&second_innovations, &mut second);
+    assert_eq!(first, second);
+    assert!(second.nodes().eq([Node(0), Node(1), Node(10), Node(11)].iter()));
+}
+
+#[test]
+fn test_json_should_preserve_innovations() {
+    use rustc_serialize::json;
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.25);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&mutator);
+    innovations.split(&mut mutator, &mut node_id, &Arc(Node(0), Node(1)));
+    let decoded: Innovations = json::decode(&json::encode(&innovations).unwrap()).unwrap();
+    assert_eq!(decoded, innovations);
+}
@@ -0,0 +1,271 @@
+extern crate rand;
+
+use self::rand::{Rng, SeedableRng};
+
+use super::checkpoint::RngState;
+use super::common::Value;
+use super::evolve;
+use super::evolve::{Evolution, Step};
+use super::mutator::Mutator;
+use super::network::NetworkBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum Topology {
+    Ring,
+    Full,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::Ring
+    }
+}
+
+impl Topology {
+    pub fn sources(&self, island: usize, islands_count: usize) -> Vec<usize> {
+        match *self {
+            Topology::Ring => if islands_count > 1 {
+                vec![(island + islands_count - 1) % islands_count]
+            } else {
+                Vec::new()
+            },
+            Topology::Full => (0..islands_count).filter(|&x| x != island).collect(),
+        }
+    }
+}
+
+pub struct Conf<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState> {
+    pub islands: &'r mut [evolve::Conf<'r, RngT>],
+    pub topology: Topology,
+    pub migration_interval: usize,
+    pub migrants_count: usize,
+}
+
+pub trait EvolveIslands {
+    fn evolve_islands<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self;
+}
+
+impl EvolveIslands for NetworkBuf {
+    fn evolve_islands<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
+        Mutator::from_network(&self.as_network()).evolve_islands(conf).as_network_buf()
+    }
+}
+
+impl EvolveIslands for Mutator {
+    fn evolve_islands<'r, RngT: 'r + Rng + SeedableRng<[u32; 4]> + RngState>(&self, conf: &'r mut Conf<'r, RngT>) -> Self {
+        let topology = conf.topology;
+        let migration_interval = conf.migration_interval;
+        let migrants_count = conf.migrants_count;
+        let islands: &'r mut [evolve::Conf<'r, RngT>] = &mut *conf.islands;
+        assert!(!islands.is_empty());
+        let mut evolutions = islands.iter_mut()
+            .map(|x| Evolution::new(x, self))
+            .collect::<Vec<_>>();
+        let mut populations = evolutions.iter_mut()
+            .map(|x| x.initialize())
+            .collect::<Vec<_>>();
+        let mut node_id = evolutions[0].node_id().clone();
+        for evolution in evolutions[1..].iter() {
+            node_id.merge(evolution.node_id());
+        }
+        let mut finished = vec![None; evolutions.len()];
+        let mut generations_count = 0;
+        while finished.iter().any(|x| x.is_none()) {
+            // Islands step one after another since they share the node id generator and
+            // their observers and checkpoint writers; each step mutates in parallel.
+            for (index, evolution) in evolutions.iter_mut().enumerate() {
+                if finished[index].is_some() {
+                    continue;
+                }
+                let population = match populations[index].take() {
+                    Some(v) => v,
+                    None => {
+                        finished[index] = Some(self.clone());
+                        continue;
+                    },
+                };
+                evolution.swap_node_id(&mut node_id);
+                let step = evolution.step(population);
+                evolution.swap_node_id(&mut node_id);
+                match step {
+                    Step::Continue(x) => populations[index] = Some(x),
+                    Step::Done(x) => finished[index] = Some(evolution.finish(x)),
+                }
+            }
+            generations_count += 1;
+            if migration_interval > 0 && generations_count % migration_interval == 0 {
+                migrate(&mut evolutions, &mut populations, topology, migrants_count);
+            }
+        }
+        for evolution in evolutions.iter_mut() {
+            evolution.swap_node_id(&mut node_id.clone());
+        }
+        finished.into_iter()
+            .zip(evolutions.iter())
+            .map(|(x, evolution)| {
+                let mutator = x.unwrap();
+                let error = evolution.error(&mutator);
+                (mutator, error)
+            })
+            .fold(None, |best: Option<(Mutator, Value)>, x| match best {
+                Some(best) if best.1 <= x.1 => Some(best),
+                _ => Some(x),
+            })
+            .unwrap().0
+    }
+}
+
+fn migrate<'c, 'f, RngT>(evolutions: &mut Vec<Evolution<'c, 'f, RngT>>,
+                         populations: &mut Vec<Option<Vec<evolve::Mutation>>>,
+                         topology: Topology, migrants_count: usize)
+        where RngT: 'c + Rng + SeedableRng<[u32; 4]> + RngState {
+    let active = (0..populations.len())
+        .filter(|&x| populations[x].is_some())
+        .collect::<Vec<_>>();
+    let emigrants = active.iter()
+        .map(|&x| evolutions[x].emigrants(populations[x].as_ref().unwrap(), migrants_count))
+        .collect::<Vec<_>>();
+    for (position, &index) in active.iter().enumerate() {
+        let migrants = topology.sources(position, active.len()).into_iter()
+            .flat_map(|x| emigrants[x].iter().cloned())
+            .collect::<Vec<_>>();
+        let population = populations[index].take().unwrap();
+        populations[index] = Some(evolutions[index].immigrate(population, migrants));
+    }
+}
+
+#[test]
+fn test_ring_topology_should_take_migrants_from_previous_island() {
+    assert_eq!(Topology::Ring.sources(0, 3), vec![2]);
+    assert_eq!(Topology::Ring.sources(2, 3), vec![1]);
+    assert_eq!(Topology::Ring.sources(0, 1), Vec::<usize>::new());
+}
+
+#[test]
+fn test_full_topology_should_take_migrants_from_all_other_islands() {
+    assert_eq!(Topology::Full.sources(1, 3), vec![0, 2]);
+}
+
+#[test]
+fn test_evolve_islands_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use self::rand::XorShiftRng;
+    use super::activation::ActivationFunction;
+    use super::apply::{Conf as ApplyConf};
+    use super::common::Node;
+    use super::crossover::CrossoverFunction;
+    use super::error::{Conf as ErrorConf, Error, MetricFunction, Sample};
+    use super::fitness::FitnessFunction;
+    use super::id_generator::IdGenerator;
+    use super::matrix::Matrix;
+    use super::mutation::{Conf as MutationConf};
+    use super::network::Network;
+    use super::selection::SelectionFunction;
+    use super::sparse_matrix::SparseMatrixBuf;
+    use super::train::{Bounds, Conf as TrainConf, Method};
+    let mut weights_values = [
+        0.0, 0.0, 0.0, 0.1, 0.1,
+        0.0, 0.0, 0.0, 0.1, 0.1,
+        0.0, 0.0, 0.0, 0.1, 0.1,
+        0.0, 0.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 0.0, 0.0,
+    ];
+    let inputs = [0, 1, 2].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [3, 4].iter().cloned().collect::<HashSet<usize>>();
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(5, &mut weights_values));
+    let weights = weights_buf.as_matrix();
+    let nodes = (0..5).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 5];
+    let biases = vec![0.0; 5];
+    let network = Network {
+        inputs: &inputs,
+        outputs: &outputs,
+        weights: weights,
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
+    };
+    let mutator = Mutator::from_network(&network);
+    let apply_conf = ApplyConf {
+        group_size: 1000,
+        threshold: 1e-4,
+        mode: None,
+        max_visits: None,
+    };
+    let samples = [
+        Sample {input: &[0.6, 0.7, 0.8], output: &[0.5, 0.4]},
+        Sample {input: &[0.3, 0.4, 0.5], output: &[0.3, 0.4]},
+    ];
+    let error_conf = ErrorConf {
+        apply_conf: &apply_conf,
+        samples: &samples,
+        metric: &MetricFunction::Euclidean,
+        custom_activations: &HashMap::new(),
+    };
+    let train_conf = TrainConf {
+        error_conf: &error_conf,
+        max_function_calls_count: 111,
+        method: Method::Bobyqa,
+        allow_new_arcs: false,
+        weight_bounds: Bounds::default(),
+        bias_bounds: Bounds::symmetric(),
+        arc_bounds: HashMap::new(),
+    };
+    let mutation_conf = MutationConf::default();
+    let mut first_rng = XorShiftRng::from_seed([1, 1, 1, 1]);
+    let mut second_rng = XorShiftRng::from_seed([2, 2, 2, 2]);
+    let mut first_node_id = IdGenerator::new(0);
+    let mut second_node_id = IdGenerator::new(0);
+    let mut islands = [
+        evolve::Conf {
+            train_conf: &train_conf,
+            mutation_conf: &mutation_conf,
+            selection: &SelectionFunction::Truncation,
+            crossover: &CrossoverFunction::Union,
+            elitism_count: 0,
+            fitness: &FitnessFunction::Error,
+            rng: &mut first_rng,
+            node_id: &mut first_node_id,
+            population_size: 2,
+            error: 1e-3,
+            iterations_count: 3,
+            validation_error_conf: None,
+            patience: None,
+            checkpoint_interval: 0,
+            checkpoint_writer: None,
+            resume_from: None,
+            observer: None,
+            speciation_conf: None,
+        },
+        evolve::Conf {
+            train_conf: &train_conf,
+            mutation_conf: &mutation_conf,
+            selection: &SelectionFunction::Tournament(2),
+            crossover: &CrossoverFunction::Aligned,
+            elitism_count: 1,
+            fitness: &FitnessFunction::Error,
+            rng: &mut second_rng,
+            node_id: &mut second_node_id,
+            population_size: 4,
+            error: 1e-3,
+            iterations_count: 2,
+            validation_error_conf: None,
+            patience: None,
+            checkpoint_interval: 0,
+            checkpoint_writer: None,
+            resume_from: None,
+            observer: None,
+            speciation_conf: None,
+        },
+    ];
+    let mut conf = Conf {
+        islands: &mut islands,
+        topology: Topology::Ring,
+        migration_interval: 1,
+        migrants_count: 1,
+    };
+    let initial_error = network.error(&error_conf);
+    let evolved = mutator.evolve_islands(&mut conf);
+    let evolved_error = evolved.as_network_buf().as_network().error(&error_conf);
+    assert!(evolved_error < initial_error);
+}
@@ -86,6 +86,14 @@ impl <T: Clone> MatrixBuf<T> {
         }
     }
 
+    pub fn from_rows(column_len: usize, rows: Vec<Vec<T>>) -> MatrixBuf<T> {
+        assert!(rows.iter().all(|x| x.len() == column_len));
+        MatrixBuf {
+            column_len: column_len,
+            values: rows.into_iter().flat_map(|x| x.into_iter()).collect::<Vec<_>>(),
+        }
+    }
+
     pub fn as_matrix<'r>(&'r self) -> Matrix<'r, T> {
         Matrix::new(self.column_len, &self.values[..])
     }
@@ -125,6 +133,13 @@ fn test_set_by_index_should_succeed() {
     assert_eq!(matrix.as_matrix().row(13)[7], 0.42);
 }
 
+#[test]
+fn test_from_rows_should_succeed() {
+    let matrix = MatrixBuf::from_rows(2, vec![vec![0.1, 0.2], vec![0.3, 0.4], vec![0.5, 0.6]]);
+    assert_eq!(matrix.as_matrix().row(1), &[0.3, 0.4]);
+    assert_eq!(matrix.as_matrix().values().len(), 6);
+}
+
 #[test]
 fn test_partial_eq_should_succeed() {
     assert_eq!(MatrixBuf::new(42, 4.2).as_matrix(),
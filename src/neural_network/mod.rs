@@ -1,24 +1,55 @@
+mod activation;
 mod apply;
+mod checkpoint;
 mod common;
+mod crossover;
 mod error;
 mod evolve;
+mod fitness;
 mod graph;
 mod id_generator;
+mod innovation;
+mod island;
 mod matrix;
+mod mutation;
 mod mutator;
 mod network;
+mod observer;
+mod pareto;
+mod selection;
+mod speciation;
+mod sparse_matrix;
 mod train;
 
+pub use self::activation::{Activation, ActivationFunction};
 pub use self::apply::Conf as ApplyConf;
-pub use self::apply::{Apply, Application};
+pub use self::apply::Mode as ApplyMode;
+pub use self::apply::Error as ApplyError;
+pub use self::apply::{Apply, Application, Diagnostics, Estimate, RecurrentApplication};
+pub use self::checkpoint::{Checkpoint, CheckpointWriter};
 pub use self::common::{Node, Value, Weight};
+pub use self::crossover::{Crossover, CrossoverFunction};
 pub use self::error::Conf as ErrorConf;
-pub use self::error::{Error, Sample};
+pub use self::error::SequenceConf as SequenceErrorConf;
+pub use self::error::{Error, Metric, MetricFunction, Sample, SequenceError};
 pub use self::evolve::Conf as EvolveConf;
-pub use self::evolve::Evolve;
+pub use self::evolve::{Evolve, EvolveFront};
+pub use self::fitness::{Complexity, Fitness, FitnessFunction, Objective, ObjectiveFunction};
 pub use self::id_generator::IdGenerator;
-pub use self::matrix::Matrix;
-pub use self::mutator::Mutator;
+pub use self::innovation::Innovations;
+pub use self::island::Conf as IslandConf;
+pub use self::island::{EvolveIslands, Topology};
+pub use self::matrix::{Matrix, MatrixBuf};
+pub use self::mutation::Conf as MutationConf;
+pub use self::mutation::{Operator as MutationOperator, WeightDistribution};
+pub use self::mutator::{Arc, Mutator};
 pub use self::network::{Network, NetworkBuf};
+pub use self::observer::{Observer, Stats};
+pub use self::pareto::{crowding_distances, dominates, non_dominated_sort};
+pub use self::selection::{Selection, SelectionFunction};
+pub use self::speciation::Conf as SpeciationConf;
+pub use self::speciation::{Speciation, Species};
+pub use self::sparse_matrix::{SparseMatrix, SparseMatrixBuf};
 pub use self::train::Conf as TrainConf;
+pub use self::train::{Bounds as TrainBounds, GradientConf, Method as TrainMethod, Optimizer};
 pub use self::train::Train;
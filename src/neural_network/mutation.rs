@@ -0,0 +1,274 @@
+extern crate rand;
+
+use self::rand::Rng;
+use self::rand::distributions::{IndependentSample, Normal};
+
+use super::common::{Node, Value, Weight};
+use super::id_generator::IdGenerator;
+use super::innovation::Innovations;
+use super::mutator::{Arc, Mutator};
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Conf {
+    pub operations_count: usize,
+    pub split_arc: Value,
+    pub add_arc: Value,
+    pub rm_arc: Value,
+    pub perturb_weight: Value,
+    pub rm_node: Value,
+    pub reverse_arc: Value,
+    pub new_arc_weight: WeightDistribution,
+    pub weight_perturbation: Value,
+}
+
+impl Default for Conf {
+    fn default() -> Self {
+        Conf {
+            operations_count: 3,
+            split_arc: 1.0,
+            add_arc: 1.0,
+            rm_arc: 1.0,
+            perturb_weight: 0.0,
+            rm_node: 0.0,
+            reverse_arc: 0.0,
+            new_arc_weight: WeightDistribution::Constant(1e-3),
+            weight_perturbation: 0.1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum WeightDistribution {
+    Constant(Weight),
+    Uniform(Weight, Weight),
+    Normal(Weight, Weight),
+}
+
+impl WeightDistribution {
+    pub fn sample<RngT: Rng>(&self, rng: &mut RngT) -> Weight {
+        match *self {
+            WeightDistribution::Constant(weight) => weight,
+            WeightDistribution::Uniform(lower, upper) => lower + (upper - lower) * rng.gen::<Weight>(),
+            WeightDistribution::Normal(mean, deviation) => {
+                Normal::new(mean, deviation).ind_sample(rng)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    SplitArc,
+    AddArc,
+    RmArc,
+    PerturbWeight,
+    RmNode,
+    ReverseArc,
+}
+
+impl Conf {
+    pub fn operators(&self) -> [(Operator, Value); 6] {
+        [
+            (Operator::SplitArc, self.split_arc),
+            (Operator::AddArc, self.add_arc),
+            (Operator::RmArc, self.rm_arc),
+            (Operator::PerturbWeight, self.perturb_weight),
+            (Operator::RmNode, self.rm_node),
+            (Operator::ReverseArc, self.reverse_arc),
+        ]
+    }
+
+    pub fn choose_operator<RngT: Rng>(&self, rng: &mut RngT) -> Option<Operator> {
+        let operators = self.operators();
+        let total = operators.iter().map(|&(_, x)| x).sum::<Value>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut value = rng.gen::<Value>() * total;
+        for &(operator, probability) in operators.iter() {
+            if value < probability {
+                return Some(operator);
+            }
+            value -= probability;
+        }
+        operators.iter().rev().find(|&&(_, x)| x > 0.0).map(|&(x, _)| x)
+    }
+
+    pub fn mutate<RngT: Rng>(&self, mutator: &mut Mutator, node_id: &mut IdGenerator,
+                             innovations: &mut Innovations, rng: &mut RngT) {
+        for _ in 0..self.operations_count {
+            if let Some(operator) = self.choose_operator(rng) {
+                self.apply(operator, mutator, node_id, innovations, rng);
+            }
+        }
+    }
+
+    pub fn apply<RngT: Rng>(&self, operator: Operator, mutator: &mut Mutator,
+                            node_id: &mut IdGenerator, innovations: &mut Innovations,
+                            rng: &mut RngT) {
+        match operator {
+            Operator::SplitArc => if let Some(arc) = mutator.random_arc(rng) {
+                innovations.split(mutator, node_id, &arc);
+            },
+            Operator::AddArc => {
+                let nodes_count = mutator.nodes().len();
+                if mutator.arcs().len() < nodes_count * nodes_count {
+                    loop {
+                        let src = mutator.random_node(rng);
+                        let dst = mutator.random_node(rng);
+                        if !mutator.graph().arcs().contains_key(&Arc(src, dst)) {
+                            let weight = self.new_arc_weight.sample(rng);
+                            mutator.add_arc(src, dst, weight);
+                            break;
+                        }
+                    }
+                }
+            },
+            Operator::RmArc => if let Some(arc) = mutator.random_arc(rng) {
+                mutator.rm_arc(&arc);
+                mutator.rm_useless();
+            },
+            Operator::PerturbWeight => if let Some(arc) = mutator.random_arc(rng) {
+                let weight = mutator.graph().arc_weight(&arc)
+                    + Normal::new(0.0, self.weight_perturbation).ind_sample(rng);
+                mutator.set_weight(&arc, weight);
+            },
+            Operator::RmNode => if let Some(node) = mutator.random_hidden_node(rng) {
+                mutator.rm_node(&node);
+                mutator.rm_useless();
+            },
+            Operator::ReverseArc => if let Some(arc) = mutator.random_arc(rng) {
+                let Arc(src, dst) = arc;
+                if !mutator.graph().arcs().contains_key(&Arc(dst, src)) {
+                    mutator.reverse_arc(&arc);
+                }
+            },
+        }
+        innovations.register_mutator(mutator);
+    }
+}
+
+trait MutatorRandom {
+    fn random_node<RngT: Rng>(&self, rng: &mut RngT) -> Node;
+    fn random_hidden_node<RngT: Rng>(&self, rng: &mut RngT) -> Option<Node>;
+    fn random_arc<RngT: Rng>(&self, rng: &mut RngT) -> Option<Arc>;
+}
+
+impl MutatorRandom for Mutator {
+    fn random_node<RngT: Rng>(&self, rng: &mut RngT) -> Node {
+        let nth = rng.gen_range(0, self.nodes().len());
+        *self.nodes().nth(nth).unwrap()
+    }
+
+    fn random_hidden_node<RngT: Rng>(&self, rng: &mut RngT) -> Option<Node> {
+        let hidden = self.nodes()
+            .filter(|x| !self.inputs().contains(x) && !self.outputs().contains(x))
+            .cloned()
+            .collect::<Vec<_>>();
+        if hidden.is_empty() {
+            return None;
+        }
+        Some(hidden[rng.gen_range(0, hidden.len())])
+    }
+
+    fn random_arc<RngT: Rng>(&self, rng: &mut RngT) -> Option<Arc> {
+        if self.arcs().len() == 0 {
+            return None;
+        }
+        let nth = rng.gen_range(0, self.arcs().len());
+        Some(self.arcs().nth(nth).unwrap().clone())
+    }
+}
+
+#[test]
+fn test_choose_operator_with_single_nonzero_probability_should_return_it() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let conf = Conf {
+        split_arc: 0.0,
+        add_arc: 0.0,
+        rm_arc: 0.0,
+        reverse_arc: 1.0,
+        ..Conf::default()
+    };
+    for _ in 0..10 {
+        assert_eq!(conf.choose_operator(&mut rng), Some(Operator::ReverseArc));
+    }
+}
+
+#[test]
+fn test_choose_operator_with_zero_probabilities_should_return_none() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let conf = Conf {
+        split_arc: 0.0,
+        add_arc: 0.0,
+        rm_arc: 0.0,
+        ..Conf::default()
+    };
+    assert_eq!(conf.choose_operator(&mut rng), None);
+}
+
+#[test]
+fn test_mutate_add_arc_should_use_new_arc_weight() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let conf = Conf {
+        operations_count: 1,
+        split_arc: 0.0,
+        rm_arc: 0.0,
+        new_arc_weight: WeightDistribution::Constant(0.42),
+        ..Conf::default()
+    };
+    conf.mutate(&mut mutator, &mut node_id, &mut Innovations::new(), &mut rng);
+    assert_eq!(mutator.arcs().count(), 2);
+    assert!(mutator.graph().arcs().values().any(|&x| x == 0.42));
+}
+
+#[test]
+fn test_mutate_perturb_weight_should_change_weight() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let conf = Conf {
+        operations_count: 1,
+        split_arc: 0.0,
+        add_arc: 0.0,
+        rm_arc: 0.0,
+        perturb_weight: 1.0,
+        ..Conf::default()
+    };
+    conf.mutate(&mut mutator, &mut node_id, &mut Innovations::new(), &mut rng);
+    assert!(mutator.graph().arc_weight(&Arc(Node(0), Node(1))) != 0.1);
+}
+
+#[test]
+fn test_mutate_rm_node_without_hidden_nodes_should_do_nothing() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let initial = mutator.clone();
+    let conf = Conf {
+        split_arc: 0.0,
+        add_arc: 0.0,
+        rm_arc: 0.0,
+        rm_node: 1.0,
+        ..Conf::default()
+    };
+    conf.mutate(&mut mutator, &mut node_id, &mut Innovations::new(), &mut rng);
+    assert_eq!(mutator, initial);
+}
+
+#[test]
+fn test_uniform_weight_distribution_should_be_within_range() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    for _ in 0..100 {
+        let weight = WeightDistribution::Uniform(-0.5, 0.5).sample(&mut rng);
+        assert!(-0.5 <= weight && weight <= 0.5);
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::btree_map;
 use std::collections::BTreeSet;
 
-use super::common::{Node, Weight};
+use super::activation::ActivationFunction;
+use super::common::{Node, Value, Weight};
 use super::graph::{Graph, NodeArcs};
 use super::id_generator::IdGenerator;
 use super::network::{Connection, Network, NetworkBuf};
@@ -18,7 +19,7 @@ pub struct Mutator {
 impl Mutator {
     pub fn new(node_id: &mut IdGenerator, inputs_count: usize,
                outputs_count: usize, weight: Weight) -> Self {
-        assert!(weight > 0.0);
+        assert!(weight != 0.0);
         let inputs = (0..inputs_count)
             .map(|_| Node(node_id.generate()))
             .collect::<BTreeSet<_>>();
@@ -44,6 +45,22 @@ impl Mutator {
         }
     }
 
+    pub fn from_graph(inputs: BTreeSet<Node>, outputs: BTreeSet<Node>, graph: Graph) -> Self {
+        Mutator {
+            inputs: inputs,
+            outputs: outputs,
+            graph: graph,
+        }
+    }
+
+    pub fn inputs(&self) -> &BTreeSet<Node> {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &BTreeSet<Node> {
+        &self.outputs
+    }
+
     pub fn graph(&self) -> &Graph {
         &self.graph
     }
@@ -66,16 +83,79 @@ impl Mutator {
         self
     }
 
+    pub fn set_weight(&mut self, arc: &Arc, weight: Weight) -> &mut Self {
+        self.graph.set_arc_weight(arc, weight);
+        self
+    }
+
+    pub fn reverse_arc(&mut self, arc: &Arc) -> &mut Self {
+        let &Arc(src, dst) = arc;
+        let weight = self.graph.arc_weight(arc);
+        self.graph.rm_arc(arc);
+        self.graph.add_arc(dst, src, weight);
+        self
+    }
+
+    pub fn rm_node(&mut self, node: &Node) -> &mut Self {
+        assert!(!self.inputs.contains(node) && !self.outputs.contains(node));
+        let arcs = {
+            let node_arcs = self.graph.node_arcs(node);
+            node_arcs.outgoing.union(&node_arcs.incoming).cloned().collect::<Vec<_>>()
+        };
+        for arc in arcs.iter() {
+            self.graph.rm_arc(arc);
+        }
+        self.graph.rm_node(node);
+        self
+    }
+
+    pub fn rename_node(&mut self, node: &Node, new: Node) -> &mut Self {
+        assert!(!self.inputs.contains(node) && !self.outputs.contains(node));
+        let arcs = {
+            let node_arcs = self.graph.node_arcs(node);
+            node_arcs.outgoing.union(&node_arcs.incoming).cloned().collect::<Vec<_>>()
+        };
+        let activation = self.graph.node_activation(node);
+        let bias = self.graph.node_bias(node);
+        self.graph.add_node(new);
+        self.graph.set_activation(new, activation);
+        self.graph.set_bias(new, bias);
+        let rename = |x: Node| if x == *node { new } else { x };
+        for arc in arcs.iter() {
+            let weight = self.graph.arc_weight(arc);
+            let &Arc(src, dst) = arc;
+            self.graph.rm_arc(arc);
+            self.graph.add_arc(rename(src), rename(dst), weight);
+        }
+        self.graph.rm_node(node);
+        self
+    }
+
+    pub fn set_activation(&mut self, node: Node, activation: ActivationFunction) -> &mut Self {
+        self.graph.set_activation(node, activation);
+        self
+    }
+
+    pub fn set_bias(&mut self, node: Node, bias: Value) -> &mut Self {
+        self.graph.set_bias(node, bias);
+        self
+    }
+
     pub fn split(&mut self, node_id: &mut IdGenerator, arc: &Arc) -> &mut Self {
         let mut middle = Node(node_id.generate());
         while self.graph.nodes().contains_key(&middle) {
             middle = Node(node_id.generate());
         }
+        self.split_at(middle, arc)
+    }
+
+    pub fn split_at(&mut self, middle: Node, arc: &Arc) -> &mut Self {
         self.graph.add_node(middle);
         let &Arc(src, dst) = arc;
-        let weight = self.graph.arcs()[arc].sqrt();
-        self.graph.add_arc(src, middle, weight);
-        self.graph.add_arc(middle, dst, weight);
+        let weight = self.graph.arcs()[arc];
+        let root = weight.abs().sqrt();
+        self.graph.add_arc(src, middle, weight.signum() * root);
+        self.graph.add_arc(middle, dst, root);
         self.graph.rm_arc(arc);
         self
     }
@@ -85,18 +165,21 @@ impl Mutator {
             Connection {src: src, dst: dst, weight: weight}
         };
         let arcs = self.graph.arcs().iter().map(&as_connection);
-        NetworkBuf::new(arcs, self.inputs.iter(), self.outputs.iter())
+        NetworkBuf::new(arcs, self.inputs.iter(), self.outputs.iter(),
+                        self.graph.activations().iter(), self.graph.biases().iter())
     }
 
     pub fn from_network(network: &Network) -> Mutator {
         let mut graph = Graph::new();
-        for (_, &node) in network.nodes.iter() {
+        for (&index, &node) in network.nodes.iter() {
             graph.add_node(node);
+            graph.set_activation(node, network.activations[index].clone());
+            graph.set_bias(node, network.biases[index]);
         }
         for (&src, &src_node) in network.nodes.iter() {
-            for (dst, &weight) in network.weights.row(src).iter().enumerate() {
-                if weight > 0.0 {
-                    graph.add_arc(src_node, network.nodes[&dst], weight);
+            for (dst, &weight) in network.weights.row(src) {
+                if weight != 0.0 {
+                    graph.add_arc(src_node, network.nodes[dst], weight);
                 }
             }
         }
@@ -146,6 +229,7 @@ fn test_new_should_succeed() {
 fn test_rm_last_arc_for_not_input_or_output_should_rm_node() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use neural_network::matrix::Matrix;
+    use neural_network::sparse_matrix::SparseMatrixBuf;
     use neural_network::network::Network;
     let mut weights_values = [
         0.0, 1.0, 0.0,
@@ -154,19 +238,24 @@ fn test_rm_last_arc_for_not_input_or_output_should_rm_node() {
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &mut weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &mut weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
     let network = Network {
         inputs: &inputs,
         outputs: &outputs,
         weights: weights,
-        nodes: &nodes
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
     };
     let mut mutator = Mutator::from_network(&network);
     mutator.rm_arc(&Arc(Node(0), Node(1)));
     let network_buf = mutator.as_network_buf();
-    let result = network_buf.as_network();
-    assert_eq!(result.weights.values(), [
+    let result = network_buf.as_network().weights.to_matrix_buf(0.0);
+    assert_eq!(result.as_matrix().values(), [
         0.0, 0.0,
         0.0, 0.0,
     ]);
@@ -176,6 +265,7 @@ fn test_rm_last_arc_for_not_input_or_output_should_rm_node() {
 fn test_rm_useless_for_node_not_input_and_not_output_without_incoming_arcs_should_rm_arc_and_node() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use neural_network::matrix::Matrix;
+    use neural_network::sparse_matrix::SparseMatrixBuf;
     use neural_network::network::Network;
     let mut weights_values = [
         0.0, 0.0, 0.0,
@@ -184,19 +274,24 @@ fn test_rm_useless_for_node_not_input_and_not_output_without_incoming_arcs_shoul
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(3, &mut weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &mut weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 3];
+    let biases = vec![0.0; 3];
     let network = Network {
         inputs: &inputs,
         outputs: &outputs,
         weights: weights,
-        nodes: &nodes
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
     };
     let mut mutator = Mutator::from_network(&network);
     mutator.rm_useless();
     let network_buf = mutator.as_network_buf();
-    let result = network_buf.as_network();
-    assert_eq!(result.weights.values(), [
+    let result = network_buf.as_network().weights.to_matrix_buf(0.0);
+    assert_eq!(result.as_matrix().values(), [
         0.0, 0.0,
         0.0, 0.0,
     ]);
@@ -206,6 +301,7 @@ fn test_rm_useless_for_node_not_input_and_not_output_without_incoming_arcs_shoul
 fn test_rm_useless_for_not_connected_only_input_and_output_nodes_should_do_nothing() {
     use std::collections::{BTreeSet, HashMap, HashSet};
     use neural_network::matrix::Matrix;
+    use neural_network::sparse_matrix::SparseMatrixBuf;
     use neural_network::network::Network;
     let mut weights_values = [
         0.0, 0.0,
@@ -213,16 +309,106 @@ fn test_rm_useless_for_not_connected_only_input_and_output_nodes_should_do_nothi
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
-    let weights = Matrix::new(2, &mut weights_values);
+    let weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &mut weights_values));
+    let weights = weights_buf.as_matrix();
     let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let biases = vec![0.0; 2];
     let network = Network {
         inputs: &inputs,
         outputs: &outputs,
         weights: weights,
-        nodes: &nodes
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
     };
     let mut mutator = Mutator::from_network(&network);
     let expected = mutator.clone();
     mutator.rm_useless();
     assert_eq!(expected, mutator);
 }
+
+#[test]
+fn test_as_network_buf_should_preserve_activations() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let arc = mutator.arcs().nth(0).unwrap().clone();
+    mutator.split(&mut node_id, &arc);
+    mutator.set_activation(Node(2), ActivationFunction::Tanh);
+    let network_buf = mutator.as_network_buf();
+    assert_eq!(Mutator::from_network(&network_buf.as_network()), mutator);
+}
+
+#[test]
+fn test_as_network_buf_should_preserve_biases() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    mutator.set_bias(Node(1), 0.3);
+    let arc = mutator.arcs().nth(0).unwrap().clone();
+    mutator.split(&mut node_id, &arc);
+    mutator.set_bias(Node(2), 0.2);
+    let network_buf = mutator.as_network_buf();
+    assert_eq!(Mutator::from_network(&network_buf.as_network()), mutator);
+}
+
+#[test]
+fn test_new_with_negative_weight_should_succeed() {
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 1, 1, -0.1);
+    assert_eq!(mutator.graph().arcs()[&Arc(Node(0), Node(1))], -0.1);
+}
+
+#[test]
+fn test_split_arc_with_negative_weight_should_keep_weights_product() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, -0.25);
+    mutator.split(&mut node_id, &Arc(Node(0), Node(1)));
+    let arcs = mutator.graph().arcs();
+    assert_eq!(arcs[&Arc(Node(0), Node(2))], -0.5);
+    assert_eq!(arcs[&Arc(Node(2), Node(1))], 0.5);
+}
+
+#[test]
+fn test_from_network_should_preserve_negative_weights() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, -0.3);
+    let arc = mutator.arcs().nth(0).unwrap().clone();
+    mutator.split(&mut node_id, &arc);
+    let network_buf = mutator.as_network_buf();
+    assert_eq!(Mutator::from_network(&network_buf.as_network()), mutator);
+}
+
+#[test]
+fn test_set_weight_should_succeed() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    mutator.set_weight(&Arc(Node(0), Node(1)), -0.2);
+    assert_eq!(mutator.graph().arcs()[&Arc(Node(0), Node(1))], -0.2);
+}
+
+#[test]
+fn test_reverse_arc_should_keep_weight() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    mutator.reverse_arc(&Arc(Node(0), Node(1)));
+    assert_eq!(mutator.arcs().cloned().collect::<Vec<_>>(), vec![Arc(Node(1), Node(0))]);
+    assert_eq!(mutator.graph().arcs()[&Arc(Node(1), Node(0))], 0.1);
+}
+
+#[test]
+fn test_rm_node_should_rm_node_and_its_arcs() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    mutator.split(&mut node_id, &Arc(Node(0), Node(1)));
+    mutator.rm_node(&Node(2));
+    assert_eq!(mutator.nodes().cloned().collect::<Vec<_>>(), vec![Node(0), Node(1)]);
+    assert_eq!(mutator.arcs().count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_rm_input_node_should_panic() {
+    let mut node_id = IdGenerator::new(0);
+    let mut mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    mutator.rm_node(&Node(0));
+}
@@ -1,22 +1,27 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 
-use super::common::{Node, Weight};
-use super::matrix::{Matrix, MatrixMut, MatrixBuf};
+use super::activation::ActivationFunction;
+use super::common::{Node, Value, Weight};
+use super::sparse_matrix::{SparseMatrix, SparseMatrixMut, SparseMatrixBuf};
 
 #[derive(Debug)]
 pub struct Network<'r> {
     pub inputs: &'r BTreeSet<usize>,
     pub outputs: &'r HashSet<usize>,
-    pub weights: Matrix<'r, Weight>,
+    pub weights: SparseMatrix<'r, Weight>,
     pub nodes: &'r HashMap<usize, Node>,
+    pub activations: &'r [ActivationFunction],
+    pub biases: &'r [Value],
 }
 
 #[derive(Debug)]
 pub struct NetworkMut<'r> {
     pub inputs: &'r BTreeSet<usize>,
     pub outputs: &'r HashSet<usize>,
-    pub weights: MatrixMut<'r, Weight>,
+    pub weights: SparseMatrixMut<'r, Weight>,
     pub nodes: &'r HashMap<usize, Node>,
+    pub activations: &'r [ActivationFunction],
+    pub biases: &'r mut [Value],
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -26,21 +31,27 @@ pub struct Connection {
     pub weight: Weight,
 }
 
-#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+#[derive(Debug, Clone, PartialEq, RustcDecodable, RustcEncodable)]
 pub struct NetworkBuf {
     inputs: BTreeSet<usize>,
     outputs: HashSet<usize>,
-    weights: MatrixBuf<Weight>,
+    weights: SparseMatrixBuf<Weight>,
     nodes: HashMap<usize, Node>,
+    activations: Vec<ActivationFunction>,
+    biases: Vec<Value>,
 }
 
 impl NetworkBuf {
-    pub fn new<'r, Connections, OrdNodeIds>(
+    pub fn new<'r, Connections, OrdNodeIds, Activations, Biases>(
             arcs: Connections,
             inputs: OrdNodeIds,
-            outputs: OrdNodeIds) -> NetworkBuf
+            outputs: OrdNodeIds,
+            activations: Activations,
+            biases: Biases) -> NetworkBuf
             where Connections: Iterator<Item=Connection> + Clone,
-                  OrdNodeIds: Iterator<Item=&'r Node> + Clone {
+                  OrdNodeIds: Iterator<Item=&'r Node> + Clone,
+                  Activations: Iterator<Item=(&'r Node, &'r ActivationFunction)>,
+                  Biases: Iterator<Item=(&'r Node, &'r Value)> {
         let mut indicies = HashMap::new();
         for node in inputs.clone() {
             NetworkBuf::add_node_index(&mut indicies, *node);
@@ -52,11 +63,19 @@ impl NetworkBuf {
         for node in outputs.clone() {
             NetworkBuf::add_node_index(&mut indicies, *node);
         }
-        let mut weights = MatrixBuf::new(indicies.len(), 0.0);
-        {
-            let mut w = weights.as_matrix_mut();
-            for arc in arcs {
-                w.set(indicies[&arc.src], indicies[&arc.dst], arc.weight);
+        let weights = SparseMatrixBuf::from_entries(
+            indicies.len(),
+            arcs.map(|arc| (indicies[&arc.src], indicies[&arc.dst], arc.weight)).collect::<_>());
+        let mut node_activations = vec![ActivationFunction::Identity; indicies.len()];
+        for (node, activation) in activations {
+            if let Some(&index) = indicies.get(node) {
+                node_activations[index] = activation.clone();
+            }
+        }
+        let mut node_biases = vec![0.0; indicies.len()];
+        for (node, &bias) in biases {
+            if let Some(&index) = indicies.get(node) {
+                node_biases[index] = bias;
             }
         }
         NetworkBuf {
@@ -64,6 +83,8 @@ impl NetworkBuf {
             outputs: outputs.map(|x| indicies[x]).collect::<_>(),
             weights: weights,
             nodes: indicies.iter().map(|(&k, &v)| (v, k)).collect::<_>(),
+            activations: node_activations,
+            biases: node_biases,
         }
     }
 
@@ -73,6 +94,8 @@ impl NetworkBuf {
             outputs: &self.outputs,
             weights: self.weights.as_matrix(),
             nodes: &self.nodes,
+            activations: &self.activations,
+            biases: &self.biases,
         }
     }
 
@@ -82,6 +105,8 @@ impl NetworkBuf {
             outputs: &self.outputs,
             weights: self.weights.as_matrix_mut(),
             nodes: &self.nodes,
+            activations: &self.activations,
+            biases: &mut self.biases,
         }
     }
 
@@ -0,0 +1,27 @@
+use super::common::Value;
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Stats {
+    pub iteration: usize,
+    pub best_error: Value,
+    pub mean_error: Value,
+    pub worst_error: Value,
+    pub validation_error: Option<Value>,
+    pub mean_nodes_count: Value,
+    pub mean_arcs_count: Value,
+    pub best_nodes_count: usize,
+    pub best_arcs_count: usize,
+    pub diversity: Value,
+    pub species_count: usize,
+    pub wall_time: Value,
+}
+
+pub trait Observer {
+    fn observe(&mut self, stats: &Stats);
+}
+
+impl<F> Observer for F where F: FnMut(&Stats) {
+    fn observe(&mut self, stats: &Stats) {
+        self(stats)
+    }
+}
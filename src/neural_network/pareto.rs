@@ -0,0 +1,118 @@
+use super::common::{Value, compare};
+
+pub fn dominates(left: &[Value], right: &[Value]) -> bool {
+    left.iter().zip(right.iter()).all(|(l, r)| l <= r)
+    && left.iter().zip(right.iter()).any(|(l, r)| l < r)
+}
+
+pub fn non_dominated_sort(objectives: &[Vec<Value>]) -> Vec<Vec<usize>> {
+    let mut dominated = vec![Vec::new(); objectives.len()];
+    let mut dominators_counts = vec![0; objectives.len()];
+    for (l, left) in objectives.iter().enumerate() {
+        for (r, right) in objectives.iter().enumerate() {
+            if dominates(left, right) {
+                dominated[l].push(r);
+            } else if dominates(right, left) {
+                dominators_counts[l] += 1;
+            }
+        }
+    }
+    let mut fronts = Vec::new();
+    let mut front = (0..objectives.len())
+        .filter(|&x| dominators_counts[x] == 0)
+        .collect::<Vec<_>>();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &x in front.iter() {
+            for &y in dominated[x].iter() {
+                dominators_counts[y] -= 1;
+                if dominators_counts[y] == 0 {
+                    next.push(y);
+                }
+            }
+        }
+        next.sort();
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+pub fn crowding_distances(objectives: &[Vec<Value>], front: &[usize]) -> Vec<Value> {
+    let mut distances = vec![0.0; front.len()];
+    if front.is_empty() {
+        return distances;
+    }
+    for objective in 0..objectives[front[0]].len() {
+        let value = |x: usize| objectives[front[x]][objective];
+        let (mut order, unbounded): (Vec<_>, Vec<_>) = (0..front.len()).partition(|&x| value(x).is_finite());
+        for x in unbounded.into_iter() {
+            distances[x] = ::std::f64::INFINITY;
+        }
+        if order.is_empty() {
+            continue;
+        }
+        order.sort_by(|&l, &r| compare(&value(l), &value(r)));
+        let last = order.len() - 1;
+        let range = value(order[last]) - value(order[0]);
+        distances[order[0]] = ::std::f64::INFINITY;
+        distances[order[last]] = ::std::f64::INFINITY;
+        if range > 0.0 {
+            for i in 1..last {
+                distances[order[i]] += (value(order[i + 1]) - value(order[i - 1])) / range;
+            }
+        }
+    }
+    distances
+}
+
+#[test]
+fn test_dominates_should_require_strictly_better_objective() {
+    assert!(dominates(&[0.1, 2.0], &[0.2, 2.0]));
+    assert!(!dominates(&[0.1, 2.0], &[0.1, 2.0]));
+    assert!(!dominates(&[0.1, 3.0], &[0.2, 2.0]));
+}
+
+#[test]
+fn test_non_dominated_sort_should_return_fronts() {
+    let objectives = vec![
+        vec![0.3, 3.0],
+        vec![0.1, 5.0],
+        vec![0.4, 4.0],
+        vec![0.2, 2.0],
+        vec![0.5, 5.0],
+    ];
+    assert_eq!(non_dominated_sort(&objectives), vec![vec![1, 3], vec![0], vec![2], vec![4]]);
+}
+
+#[test]
+fn test_crowding_distances_should_prefer_boundary_and_sparse_solutions() {
+    let objectives = vec![
+        vec![0.0, 4.0],
+        vec![1.0, 3.0],
+        vec![3.0, 1.0],
+        vec![4.0, 0.0],
+    ];
+    let distances = crowding_distances(&objectives, &[0, 1, 2, 3]);
+    assert_eq!(distances[0], ::std::f64::INFINITY);
+    assert_eq!(distances[3], ::std::f64::INFINITY);
+    assert_eq!(distances[1], 3.0 / 4.0 + 3.0 / 4.0);
+    assert_eq!(distances[2], 3.0 / 4.0 + 3.0 / 4.0);
+}
+
+#[test]
+fn test_crowding_distances_with_infinite_objective_should_not_give_nan() {
+    use std::f64::INFINITY;
+    let objectives = vec![
+        vec![0.0, 4.0],
+        vec![1.0, 3.0],
+        vec![INFINITY, 2.0],
+        vec![3.0, 1.0],
+        vec![INFINITY, 0.0],
+    ];
+    let distances = crowding_distances(&objectives, &[0, 1, 2, 3, 4]);
+    assert!(distances.iter().all(|x| !x.is_nan()));
+    assert_eq!(distances[2], INFINITY);
+    assert_eq!(distances[4], INFINITY);
+    assert_eq!(distances[1], 3.0 / 3.0 + 2.0 / 4.0);
+}
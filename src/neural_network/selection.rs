@@ -0,0 +1,130 @@
+extern crate rand;
+
+use self::rand::Rng;
+
+use super::common::{Value, compare};
+
+pub trait Selection: Sync {
+    fn select(&self, errors: &[Value], count: usize, rng: &mut Rng) -> Vec<usize>;
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum SelectionFunction {
+    Truncation,
+    Tournament(usize),
+    Roulette,
+    Rank,
+}
+
+impl Default for SelectionFunction {
+    fn default() -> Self {
+        SelectionFunction::Truncation
+    }
+}
+
+impl Selection for SelectionFunction {
+    fn select(&self, errors: &[Value], count: usize, rng: &mut Rng) -> Vec<usize> {
+        let mut rng = rng;
+        match *self {
+            SelectionFunction::Truncation => {
+                sorted_indices(errors).into_iter().take(count).collect()
+            },
+            SelectionFunction::Tournament(size) => {
+                assert!(size > 0);
+                (0..count)
+                    .map(|_| {
+                        (0..size)
+                            .map(|_| (&mut rng).gen_range(0, errors.len()))
+                            .fold(None, |best: Option<usize>, x| match best {
+                                Some(best) if errors[best] <= errors[x] => Some(best),
+                                _ => Some(x),
+                            })
+                            .unwrap()
+                    })
+                    .collect()
+            },
+            SelectionFunction::Roulette => {
+                let weights = errors.iter().map(|x| 1.0 / (1.0 + x)).collect::<Vec<_>>();
+                (0..count).map(|_| spin(&weights, &mut rng)).collect()
+            },
+            SelectionFunction::Rank => {
+                let mut weights = vec![0.0; errors.len()];
+                for (rank, index) in sorted_indices(errors).into_iter().enumerate() {
+                    weights[index] = (errors.len() - rank) as Value;
+                }
+                (0..count).map(|_| spin(&weights, &mut rng)).collect()
+            },
+        }
+    }
+}
+
+fn sorted_indices(errors: &[Value]) -> Vec<usize> {
+    let mut indices = (0..errors.len()).collect::<Vec<_>>();
+    indices.sort_by(|&l, &r| compare(&errors[l], &errors[r]));
+    indices
+}
+
+fn spin<RngT: Rng>(weights: &[Value], rng: &mut RngT) -> usize {
+    let total = weights.iter().sum::<Value>();
+    let mut value = rng.gen::<Value>() * total;
+    for (index, &weight) in weights.iter().enumerate() {
+        if value < weight {
+            return index;
+        }
+        value -= weight;
+    }
+    weights.len() - 1
+}
+
+#[test]
+fn test_truncation_should_select_best() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let errors = [0.3, 0.1, 0.4, 0.2];
+    assert_eq!(SelectionFunction::Truncation.select(&errors, 2, &mut rng), vec![1, 3]);
+}
+
+#[test]
+fn test_tournament_with_whole_population_size_should_mostly_select_best() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let errors = [0.3, 0.1, 0.4, 0.2];
+    let selected = SelectionFunction::Tournament(16).select(&errors, 10, &mut rng);
+    assert_eq!(selected.len(), 10);
+    assert!(selected.iter().filter(|&&x| x == 1).count() > 5);
+}
+
+#[test]
+fn test_roulette_should_select_valid_indices() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let errors = [0.3, 0.1, 0.4, 0.2];
+    let selected = SelectionFunction::Roulette.select(&errors, 100, &mut rng);
+    assert_eq!(selected.len(), 100);
+    assert!(selected.iter().all(|&x| x < errors.len()));
+}
+
+#[test]
+fn test_rank_should_prefer_better_individuals() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let errors = [0.3, 0.1, 0.4, 0.2];
+    let selected = SelectionFunction::Rank.select(&errors, 1000, &mut rng);
+    let best = selected.iter().filter(|&&x| x == 1).count();
+    let worst = selected.iter().filter(|&&x| x == 2).count();
+    assert!(best > worst);
+}
+
+#[test]
+fn test_custom_selection_should_be_usable_as_trait_object() {
+    use self::rand::{SeedableRng, XorShiftRng};
+    struct First;
+    impl Selection for First {
+        fn select(&self, _: &[Value], count: usize, _: &mut Rng) -> Vec<usize> {
+            vec![0; count]
+        }
+    }
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let selection: &Selection = &First;
+    assert_eq!(selection.select(&[0.1, 0.2], 2, &mut rng), vec![0, 0]);
+}
@@ -0,0 +1,359 @@
+use std::iter::Zip;
+use std::slice;
+
+use rustc_serialize::{Decodable, Decoder};
+
+use super::matrix::{Matrix, MatrixBuf};
+
+pub type SparseRow<'r, T> = Zip<slice::Iter<'r, usize>, slice::Iter<'r, T>>;
+
+#[derive(Clone, Debug)]
+pub struct SparseMatrix<'r, T: 'r> {
+    column_len: usize,
+    offsets: &'r [usize],
+    columns: &'r [usize],
+    values: &'r [T],
+}
+
+impl <'r, T: Clone> SparseMatrix<'r, T> {
+    pub fn new(column_len: usize, offsets: &'r [usize], columns: &'r [usize],
+               values: &'r [T]) -> SparseMatrix<'r, T> {
+        assert_eq!(offsets.len(), column_len + 1);
+        assert_eq!(columns.len(), values.len());
+        SparseMatrix {
+            column_len: column_len,
+            offsets: offsets,
+            columns: columns,
+            values: values,
+        }
+    }
+
+    pub fn column_len(&self) -> usize {
+        self.column_len
+    }
+
+    pub fn values(&self) -> &[T] {
+        self.values
+    }
+
+    pub fn row(&self, row: usize) -> SparseRow<'r, T> {
+        let columns: &'r [usize] = self.columns;
+        let values: &'r [T] = self.values;
+        let range = self.offsets[row]..self.offsets[row + 1];
+        columns[range.clone()].iter().zip(values[range].iter())
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<&T> {
+        let begin = self.offsets[row];
+        let end = self.offsets[row + 1];
+        self.columns[begin..end].binary_search(&column).ok().map(|x| &self.values[begin + x])
+    }
+
+    pub fn with_values<'a>(&self, values: &'a [T]) -> SparseMatrix<'a, T> where 'r: 'a {
+        SparseMatrix::new(self.column_len, self.offsets, self.columns, values)
+    }
+
+    pub fn to_matrix_buf(&self, zero: T) -> MatrixBuf<T> {
+        let mut result = MatrixBuf::new(self.column_len, zero);
+        {
+            let mut matrix = result.as_matrix_mut();
+            for row in 0..self.column_len {
+                for (&column, value) in self.row(row) {
+                    matrix.set(row, column, value.clone());
+                }
+            }
+        }
+        result
+    }
+}
+
+impl <'a, T: PartialEq> PartialEq for SparseMatrix<'a, T> {
+    fn eq(&self, other: &SparseMatrix<'a, T>) -> bool {
+        self.column_len == other.column_len
+        && self.offsets == other.offsets
+        && self.columns == other.columns
+        && self.values == other.values
+    }
+}
+
+#[derive(Debug)]
+pub struct SparseMatrixMut<'r, T: 'r> {
+    column_len: usize,
+    offsets: &'r mut Vec<usize>,
+    columns: &'r mut Vec<usize>,
+    values: &'r mut Vec<T>,
+}
+
+impl <'r, T: Clone> SparseMatrixMut<'r, T> {
+    pub fn column_len(&self) -> usize {
+        self.column_len
+    }
+
+    pub fn values(&mut self) -> &mut [T] {
+        &mut self.values[..]
+    }
+
+    pub fn as_matrix<'a>(&'a self) -> SparseMatrix<'a, T> {
+        SparseMatrix::new(self.column_len, &self.offsets[..], &self.columns[..], &self.values[..])
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: T) {
+        let begin = self.offsets[row];
+        let end = self.offsets[row + 1];
+        match self.columns[begin..end].binary_search(&column) {
+            Ok(x) => self.values[begin + x] = value,
+            Err(x) => {
+                self.columns.insert(begin + x, column);
+                self.values.insert(begin + x, value);
+                for offset in self.offsets[row + 1..].iter_mut() {
+                    *offset += 1;
+                }
+            },
+        }
+    }
+
+    pub fn remove(&mut self, row: usize, column: usize) {
+        let begin = self.offsets[row];
+        let end = self.offsets[row + 1];
+        if let Ok(x) = self.columns[begin..end].binary_search(&column) {
+            self.columns.remove(begin + x);
+            self.values.remove(begin + x);
+            for offset in self.offsets[row + 1..].iter_mut() {
+                *offset -= 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RustcEncodable)]
+pub struct SparseMatrixBuf<T> {
+    column_len: usize,
+    offsets: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<T>,
+}
+
+impl <T: Clone> SparseMatrixBuf<T> {
+    pub fn from_entries(column_len: usize, mut entries: Vec<(usize, usize, T)>) -> SparseMatrixBuf<T> {
+        entries.sort_by(|&(lr, lc, _), &(rr, rc, _)| (lr, lc).cmp(&(rr, rc)));
+        for pair in entries.windows(2) {
+            assert!((pair[0].0, pair[0].1) != (pair[1].0, pair[1].1),
+                    "duplicate entry ({}, {})", pair[0].0, pair[0].1);
+        }
+        let mut offsets = Vec::with_capacity(column_len + 1);
+        let mut columns = Vec::with_capacity(entries.len());
+        let mut values = Vec::with_capacity(entries.len());
+        offsets.push(0);
+        for (row, column, value) in entries.into_iter() {
+            assert!(row < column_len && column < column_len);
+            while offsets.len() <= row {
+                offsets.push(columns.len());
+            }
+            columns.push(column);
+            values.push(value);
+        }
+        while offsets.len() <= column_len {
+            offsets.push(columns.len());
+        }
+        SparseMatrixBuf {
+            column_len: column_len,
+            offsets: offsets,
+            columns: columns,
+            values: values,
+        }
+    }
+
+    pub fn as_matrix<'r>(&'r self) -> SparseMatrix<'r, T> {
+        SparseMatrix::new(self.column_len, &self.offsets[..], &self.columns[..], &self.values[..])
+    }
+
+    fn check(&self) -> Result<(), &'static str> {
+        if self.offsets.len() != self.column_len + 1 {
+            return Err("offsets length must be column_len + 1");
+        }
+        if self.columns.len() != self.values.len() {
+            return Err("columns and values lengths must be equal");
+        }
+        if self.offsets[0] != 0 || self.offsets[self.column_len] != self.columns.len() {
+            return Err("offsets must start at 0 and end at columns length");
+        }
+        for row in 0..self.column_len {
+            let (begin, end) = (self.offsets[row], self.offsets[row + 1]);
+            if begin > end {
+                return Err("offsets must not decrease");
+            }
+            let columns = &self.columns[begin..end];
+            if columns.iter().any(|&x| x >= self.column_len) {
+                return Err("column is out of bounds");
+            }
+            if columns.windows(2).any(|x| x[0] >= x[1]) {
+                return Err("columns in a row must be strictly increasing");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn as_matrix_mut<'r>(&'r mut self) -> SparseMatrixMut<'r, T> {
+        SparseMatrixMut {
+            column_len: self.column_len,
+            offsets: &mut self.offsets,
+            columns: &mut self.columns,
+            values: &mut self.values,
+        }
+    }
+}
+
+#[derive(RustcDecodable)]
+struct SparseMatrixData<T> {
+    column_len: usize,
+    offsets: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<T>,
+}
+
+impl <T: Clone + Decodable> Decodable for SparseMatrixBuf<T> {
+    fn decode<D: Decoder>(d: &mut D) -> Result<SparseMatrixBuf<T>, D::Error> {
+        let data = SparseMatrixData::decode(d)?;
+        let result = SparseMatrixBuf {
+            column_len: data.column_len,
+            offsets: data.offsets,
+            columns: data.columns,
+            values: data.values,
+        };
+        match result.check() {
+            Ok(()) => Ok(result),
+            Err(e) => Err(d.error(e)),
+        }
+    }
+}
+
+impl <T: Clone + Default + PartialEq> SparseMatrixBuf<T> {
+    pub fn from_matrix(matrix: &Matrix<T>) -> SparseMatrixBuf<T> {
+        let zero = T::default();
+        let column_len = matrix.column_len();
+        let entries = (0..column_len)
+            .flat_map(|row| {
+                matrix.row(row).iter()
+                    .enumerate()
+                    .filter(|&(_, value)| *value != zero)
+                    .map(|(column, value)| (row, column, value.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        SparseMatrixBuf::from_entries(column_len, entries)
+    }
+}
+
+#[test]
+fn test_from_entries_should_succeed() {
+    SparseMatrixBuf::<f64>::from_entries(42, Vec::new());
+}
+
+#[test]
+#[should_panic]
+fn test_from_entries_should_reject_duplicates() {
+    SparseMatrixBuf::from_entries(2, vec![(0, 1, 0.1), (0, 1, 0.2)]);
+}
+
+#[test]
+fn test_decode_should_validate_structure() {
+    use rustc_serialize::json;
+    let matrix = SparseMatrixBuf::from_entries(3, vec![(0, 2, 0.25), (0, 1, 0.5), (2, 1, 1.0)]);
+    let decoded: SparseMatrixBuf<f64> = json::decode(&json::encode(&matrix).unwrap()).unwrap();
+    assert_eq!(decoded, matrix);
+    for invalid in [
+        r#"{"column_len":2,"offsets":[0,1],"columns":[1],"values":[0.1]}"#,
+        r#"{"column_len":2,"offsets":[0,1,1],"columns":[1],"values":[]}"#,
+        r#"{"column_len":2,"offsets":[1,1,1],"columns":[1],"values":[0.1]}"#,
+        r#"{"column_len":2,"offsets":[0,2,1],"columns":[0,1],"values":[0.1,0.2]}"#,
+        r#"{"column_len":2,"offsets":[0,1,1],"columns":[2],"values":[0.1]}"#,
+        r#"{"column_len":2,"offsets":[0,2,2],"columns":[1,0],"values":[0.1,0.2]}"#,
+        r#"{"column_len":2,"offsets":[0,2,2],"columns":[1,1],"values":[0.1,0.2]}"#,
+    ].iter() {
+        assert!(json::decode::<SparseMatrixBuf<f64>>(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_column_len_should_succeed() {
+    assert_eq!(SparseMatrixBuf::<f64>::from_entries(42, Vec::new()).as_matrix().column_len(), 42);
+}
+
+#[test]
+fn test_from_entries_should_sort_by_row_and_column() {
+    let matrix = SparseMatrixBuf::from_entries(3, vec![(2, 0, 0.3), (0, 2, 0.2), (0, 1, 0.1)]);
+    assert_eq!(matrix.as_matrix().values(), &[0.1, 0.2, 0.3]);
+    assert_eq!(matrix.as_matrix().row(0).map(|(&c, &v)| (c, v)).collect::<Vec<_>>(),
+               vec![(1, 0.1), (2, 0.2)]);
+    assert_eq!(matrix.as_matrix().row(1).count(), 0);
+    assert_eq!(matrix.as_matrix().row(2).map(|(&c, &v)| (c, v)).collect::<Vec<_>>(),
+               vec![(0, 0.3)]);
+}
+
+#[test]
+fn test_get_should_succeed() {
+    let matrix = SparseMatrixBuf::from_entries(3, vec![(1, 2, 0.42)]);
+    assert_eq!(matrix.as_matrix().get(1, 2), Some(&0.42));
+    assert_eq!(matrix.as_matrix().get(2, 1), None);
+}
+
+#[test]
+fn test_set_should_insert_absent_value() {
+    let mut matrix = SparseMatrixBuf::from_entries(3, vec![(0, 0, 0.1), (2, 2, 0.3)]);
+    matrix.as_matrix_mut().set(1, 1, 0.2);
+    assert_eq!(matrix.as_matrix().values(), &[0.1, 0.2, 0.3]);
+    assert_eq!(matrix.as_matrix().get(1, 1), Some(&0.2));
+    assert_eq!(matrix.as_matrix().get(2, 2), Some(&0.3));
+}
+
+#[test]
+fn test_set_should_replace_present_value() {
+    let mut matrix = SparseMatrixBuf::from_entries(3, vec![(1, 1, 0.2)]);
+    matrix.as_matrix_mut().set(1, 1, 0.42);
+    assert_eq!(matrix.as_matrix().values(), &[0.42]);
+}
+
+#[test]
+fn test_remove_should_succeed() {
+    let mut matrix = SparseMatrixBuf::from_entries(3, vec![(0, 0, 0.1), (1, 1, 0.2), (2, 2, 0.3)]);
+    matrix.as_matrix_mut().remove(1, 1);
+    assert_eq!(matrix.as_matrix().values(), &[0.1, 0.3]);
+    assert_eq!(matrix.as_matrix().get(1, 1), None);
+    assert_eq!(matrix.as_matrix().get(2, 2), Some(&0.3));
+}
+
+#[test]
+fn test_from_matrix_should_skip_zeros() {
+    let values = [
+        0.0, 0.4,
+        0.0, 0.0,
+    ];
+    let matrix = SparseMatrixBuf::from_matrix(&Matrix::new(2, &values));
+    assert_eq!(matrix.as_matrix().values(), &[0.4]);
+    assert_eq!(matrix.as_matrix().get(0, 1), Some(&0.4));
+}
+
+#[test]
+fn test_to_matrix_buf_should_succeed() {
+    let values = [
+        0.0, 0.4,
+        0.2, 0.0,
+    ];
+    let matrix = SparseMatrixBuf::from_matrix(&Matrix::new(2, &values));
+    assert_eq!(matrix.as_matrix().to_matrix_buf(0.0).as_matrix(), Matrix::new(2, &values));
+}
+
+#[test]
+fn test_with_values_should_keep_structure() {
+    let matrix = SparseMatrixBuf::from_entries(3, vec![(0, 2, 0.1), (2, 1, 0.2)]);
+    let values = [0.3, 0.4];
+    let other = matrix.as_matrix().with_values(&values);
+    assert_eq!(other.get(0, 2), Some(&0.3));
+    assert_eq!(other.get(2, 1), Some(&0.4));
+}
+
+#[test]
+fn test_partial_eq_should_succeed() {
+    assert_eq!(SparseMatrixBuf::from_entries(2, vec![(0, 1, 4.2)]).as_matrix(),
+               SparseMatrixBuf::from_entries(2, vec![(0, 1, 4.2)]).as_matrix());
+}
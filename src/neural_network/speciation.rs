@@ -0,0 +1,238 @@
+use super::common::{Value, compare};
+use super::innovation::Innovations;
+use super::mutator::Mutator;
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Conf {
+    pub excess_coefficient: Value,
+    pub disjoint_coefficient: Value,
+    pub weight_coefficient: Value,
+    pub threshold: Value,
+    pub stagnation_limit: usize,
+}
+
+impl Default for Conf {
+    fn default() -> Self {
+        Conf {
+            excess_coefficient: 1.0,
+            disjoint_coefficient: 1.0,
+            weight_coefficient: 0.4,
+            threshold: 3.0,
+            stagnation_limit: 15,
+        }
+    }
+}
+
+impl Conf {
+    pub fn distance(&self, innovations: &Innovations, left: &Mutator, right: &Mutator) -> Value {
+        let max_number = |mutator: &Mutator| {
+            mutator.arcs().filter_map(|x| innovations.number(x)).max()
+        };
+        let left_max = max_number(left);
+        let right_max = max_number(right);
+        let mut excess = 0;
+        let mut disjoint = 0;
+        let mut matching = 0;
+        let mut weights_difference = 0.0;
+        for (arc, &weight) in left.graph().arcs().iter() {
+            match right.graph().arcs().get(arc) {
+                Some(&other) => {
+                    matching += 1;
+                    weights_difference += (weight - other).abs();
+                },
+                None => if innovations.number(arc) > right_max {
+                    excess += 1;
+                } else {
+                    disjoint += 1;
+                },
+            }
+        }
+        for arc in right.arcs() {
+            if !left.graph().arcs().contains_key(arc) {
+                if innovations.number(arc) > left_max {
+                    excess += 1;
+                } else {
+                    disjoint += 1;
+                }
+            }
+        }
+        let genes_count = left.arcs().len().max(right.arcs().len()).max(1) as Value;
+        let mean_weights_difference = if matching > 0 {
+            weights_difference / matching as Value
+        } else {
+            0.0
+        };
+        self.excess_coefficient * excess as Value / genes_count
+        + self.disjoint_coefficient * disjoint as Value / genes_count
+        + self.weight_coefficient * mean_weights_difference
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Species {
+    pub representative: Mutator,
+    pub best_error: Value,
+    pub stagnant_iterations_count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Speciation {
+    species: Vec<Species>,
+}
+
+impl Speciation {
+    pub fn new() -> Speciation {
+        Speciation {species: Vec::new()}
+    }
+
+    pub fn from_species(species: Vec<Species>) -> Speciation {
+        Speciation {species: species}
+    }
+
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    pub fn assign(&mut self, conf: &Conf, innovations: &Innovations,
+                  population: &[(&Mutator, Value)]) -> Vec<usize> {
+        let mut membership = Vec::with_capacity(population.len());
+        let mut members = vec![Vec::new(); self.species.len()];
+        for (index, &(mutator, _)) in population.iter().enumerate() {
+            let found = self.species.iter()
+                .position(|x| conf.distance(innovations, &x.representative, mutator) < conf.threshold);
+            let species = match found {
+                Some(species) => species,
+                None => {
+                    self.species.push(Species {
+                        representative: mutator.clone(),
+                        best_error: ::std::f64::INFINITY,
+                        stagnant_iterations_count: 0,
+                    });
+                    members.push(Vec::new());
+                    self.species.len() - 1
+                },
+            };
+            members[species].push(index);
+            membership.push(species);
+        }
+        let mut new_indices = vec![0; self.species.len()];
+        let mut species = Vec::new();
+        for (index, (mut x, members)) in self.species.drain(..).zip(members.into_iter()).enumerate() {
+            if members.is_empty() {
+                continue;
+            }
+            let best = *members.iter()
+                .min_by(|&&l, &&r| compare(&population[l].1, &population[r].1))
+                .unwrap();
+            if population[best].1 < x.best_error {
+                x.best_error = population[best].1;
+                x.stagnant_iterations_count = 0;
+            } else {
+                x.stagnant_iterations_count += 1;
+            }
+            x.representative = population[best].0.clone();
+            new_indices[index] = species.len();
+            species.push(x);
+        }
+        self.species = species;
+        membership.into_iter().map(|x| new_indices[x]).collect()
+    }
+
+    pub fn shared_errors(&self, membership: &[usize], errors: &[Value]) -> Vec<Value> {
+        let mut sizes = vec![0; self.species.len()];
+        for &species in membership.iter() {
+            sizes[species] += 1;
+        }
+        membership.iter()
+            .zip(errors.iter())
+            .map(|(&species, &error)| error * sizes[species] as Value)
+            .collect()
+    }
+
+    pub fn stagnant(&self, conf: &Conf) -> Vec<bool> {
+        let best = self.species.iter()
+            .enumerate()
+            .min_by(|&(_, l), &(_, r)| compare(&l.best_error, &r.best_error))
+            .map(|(index, _)| index);
+        self.species.iter()
+            .enumerate()
+            .map(|(index, x)| {
+                Some(index) != best && x.stagnant_iterations_count >= conf.stagnation_limit
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_distance_between_same_mutators_should_be_zero() {
+    use super::id_generator::IdGenerator;
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 2, 2, 0.1);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&mutator);
+    assert_eq!(Conf::default().distance(&innovations, &mutator, &mutator), 0.0);
+}
+
+#[test]
+fn test_distance_should_count_excess_disjoint_and_weights() {
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut node_id = IdGenerator::new(0);
+    let initial = Mutator::new(&mut node_id, 1, 2, 0.1);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&initial);
+    let mut left = initial.clone();
+    left.rm_arc(&Arc(Node(0), Node(1)));
+    left.add_arc(Node(1), Node(2), 0.1);
+    innovations.register_mutator(&left);
+    let mut right = initial.clone();
+    right.set_weight(&Arc(Node(0), Node(2)), 0.3);
+    let conf = Conf {
+        excess_coefficient: 1.0,
+        disjoint_coefficient: 2.0,
+        weight_coefficient: 4.0,
+        ..Conf::default()
+    };
+    assert_eq!(conf.distance(&innovations, &left, &right),
+               1.0 * 1.0 / 2.0 + 2.0 * 1.0 / 2.0 + 4.0 * (0.3f64 - 0.1).abs());
+}
+
+#[test]
+fn test_assign_should_split_population_by_threshold() {
+    use super::common::Node;
+    use super::id_generator::IdGenerator;
+    use super::mutator::Arc;
+    let mut node_id = IdGenerator::new(0);
+    let first = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let mut second = first.clone();
+    second.set_weight(&Arc(Node(0), Node(1)), 0.2);
+    let mut third = first.clone();
+    third.rm_arc(&Arc(Node(0), Node(1)));
+    third.add_arc(Node(1), Node(0), 0.1);
+    let mut innovations = Innovations::new();
+    innovations.register_mutator(&first);
+    innovations.register_mutator(&third);
+    let conf = Conf {threshold: 0.5, ..Conf::default()};
+    let mut speciation = Speciation::new();
+    let population = [(&first, 0.3), (&second, 0.2), (&third, 0.1)];
+    let membership = speciation.assign(&conf, &innovations, &population);
+    assert_eq!(membership, vec![0, 0, 1]);
+    assert_eq!(speciation.species().len(), 2);
+    assert_eq!(speciation.species()[0].representative, second);
+    assert_eq!(speciation.species()[0].best_error, 0.2);
+    assert_eq!(speciation.shared_errors(&membership, &[0.3, 0.2, 0.1]), vec![0.6, 0.4, 0.1]);
+}
+
+#[test]
+fn test_stagnant_should_mark_species_without_improvement_except_best() {
+    use super::id_generator::IdGenerator;
+    let mut node_id = IdGenerator::new(0);
+    let mutator = Mutator::new(&mut node_id, 1, 1, 0.1);
+    let speciation = Speciation::from_species(vec![
+        Species {representative: mutator.clone(), best_error: 0.1, stagnant_iterations_count: 20},
+        Species {representative: mutator.clone(), best_error: 0.2, stagnant_iterations_count: 20},
+        Species {representative: mutator.clone(), best_error: 0.3, stagnant_iterations_count: 1},
+    ]);
+    assert_eq!(speciation.stagnant(&Conf::default()), vec![false, true, false]);
+}
@@ -2,15 +2,67 @@ extern crate bobyqa;
 
 use self::bobyqa::Bobyqa;
 
+use std::collections::HashMap;
+
 use super::common::Value;
-use super::error::Error;
+use super::error::{Error, Sample};
 use super::error;
-use super::matrix::Matrix;
+use super::graph::Arc;
 use super::network::{Network, NetworkMut};
 
+const BOUND: Value = 1.0;
+
 pub struct Conf<'r> {
     pub error_conf: &'r error::Conf<'r>,
     pub max_function_calls_count: usize,
+    pub method: Method,
+    pub allow_new_arcs: bool,
+    pub weight_bounds: Bounds,
+    pub bias_bounds: Bounds,
+    pub arc_bounds: HashMap<Arc, Bounds>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct Bounds {
+    pub lower: Value,
+    pub upper: Value,
+}
+
+impl Bounds {
+    pub fn symmetric() -> Self {
+        Bounds {lower: -BOUND, upper: BOUND}
+    }
+
+    pub fn clamp(&self, value: Value) -> Value {
+        value.max(self.lower).min(self.upper)
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds::symmetric()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Method {
+    Bobyqa,
+    Gradient(GradientConf),
+}
+
+#[derive(Clone, Debug)]
+pub struct GradientConf {
+    pub optimizer: Optimizer,
+    pub learning_rate: Value,
+    pub batch_size: usize,
+    pub epochs_count: usize,
+    pub step: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum Optimizer {
+    Sgd,
+    Adam,
 }
 
 pub trait Train {
@@ -19,21 +71,148 @@ pub trait Train {
 
 impl<'network> Train for NetworkMut<'network> {
     fn train<'r>(&mut self, conf: &Conf) -> Value {
-        use std::iter::repeat;
-        let nodes_count = self.weights.column_len();
-        let inputs = &self.inputs;
-        let outputs = &self.outputs;
-        let nodes = &self.nodes;
-        let error_function = |weights_values: &[Value]| {
-            assert!(weights_values.len() >= nodes_count * nodes_count);
-            let weights = Matrix::new(nodes_count, weights_values);
-            let network = Network {inputs: inputs, outputs: outputs,
-                                   weights: weights, nodes: nodes};
-            network.error(conf.error_conf)
-        };
-        let variables_count = self.weights.values().len();
-        let lower_bound = repeat(0.0).take(variables_count).collect::<Vec<_>>();
-        let upper_bound = repeat(1.0).take(variables_count).collect::<Vec<_>>();
+        if conf.allow_new_arcs {
+            add_absent_arcs(self);
+        }
+        let error = match conf.method {
+            Method::Bobyqa => train_bobyqa(self, conf),
+            Method::Gradient(ref gradient_conf) => train_gradient(self, conf, gradient_conf),
+        };
+        if conf.allow_new_arcs {
+            rm_zero_arcs(self);
+        }
+        error
+    }
+}
+
+fn add_absent_arcs(network: &mut NetworkMut) {
+    let nodes_count = network.weights.column_len();
+    for src in 0..nodes_count {
+        for dst in 0..nodes_count {
+            if network.weights.as_matrix().get(src, dst).is_none() {
+                network.weights.set(src, dst, 0.0);
+            }
+        }
+    }
+}
+
+fn rm_zero_arcs(network: &mut NetworkMut) {
+    let zeros = {
+        let weights = network.weights.as_matrix();
+        (0..weights.column_len())
+            .flat_map(|src| {
+                weights.row(src)
+                    .filter(|&(_, &weight)| weight == 0.0)
+                    .map(|(&dst, _)| (src, dst))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    for (src, dst) in zeros.into_iter() {
+        network.weights.remove(src, dst);
+    }
+}
+
+struct Variables {
+    weights_count: usize,
+    bias_nodes: Vec<usize>,
+    initial: Vec<Value>,
+    free: Vec<usize>,
+    bounds: Vec<Bounds>,
+}
+
+impl Variables {
+    fn new(network: &NetworkMut, conf: &Conf) -> Variables {
+        let weights = network.weights.as_matrix();
+        let bias_nodes = (0..network.biases.len())
+            .filter(|x| !network.inputs.contains(x))
+            .collect::<Vec<_>>();
+        let mut bounds = (0..weights.column_len())
+            .flat_map(|src| {
+                weights.row(src)
+                    .map(|(&dst, _)| {
+                        let arc = Arc(network.nodes[&src], network.nodes[&dst]);
+                        conf.arc_bounds.get(&arc).cloned().unwrap_or(conf.weight_bounds)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        bounds.extend(bias_nodes.iter().map(|_| conf.bias_bounds));
+        let initial = weights.values().iter()
+            .chain(bias_nodes.iter().map(|&x| &network.biases[x]))
+            .zip(bounds.iter())
+            .map(|(&value, bounds)| bounds.clamp(value))
+            .collect::<Vec<_>>();
+        let free = (0..bounds.len())
+            .filter(|&x| bounds[x].lower < bounds[x].upper)
+            .collect::<Vec<_>>();
+        Variables {
+            weights_count: weights.values().len(),
+            bias_nodes: bias_nodes,
+            initial: initial,
+            bounds: free.iter().map(|&x| bounds[x]).collect(),
+            free: free,
+        }
+    }
+
+    fn values(&self) -> Vec<Value> {
+        self.free.iter().map(|&x| self.initial[x]).collect()
+    }
+
+    fn all_values(&self, values: &[Value]) -> Vec<Value> {
+        let mut result = self.initial.clone();
+        for (&index, &value) in self.free.iter().zip(values.iter()) {
+            result[index] = value;
+        }
+        result
+    }
+
+    fn error(&self, network: &NetworkMut, values: &[Value], error_conf: &error::Conf) -> Value {
+        let values = self.all_values(values);
+        let mut biases = network.biases.to_vec();
+        for (&node, &value) in self.bias_nodes.iter().zip(values[self.weights_count..].iter()) {
+            biases[node] = value;
+        }
+        let weights = network.weights.as_matrix();
+        let network = Network {
+            inputs: network.inputs,
+            outputs: network.outputs,
+            weights: weights.with_values(&values[0..self.weights_count]),
+            nodes: network.nodes,
+            activations: network.activations,
+            biases: &biases,
+        };
+        network.error(error_conf)
+    }
+
+    fn write(&self, network: &mut NetworkMut, values: &[Value]) {
+        let values = self.all_values(values);
+        network.weights.values().copy_from_slice(&values[0..self.weights_count]);
+        for (&node, &value) in self.bias_nodes.iter().zip(values[self.weights_count..].iter()) {
+            network.biases[node] = value;
+        }
+    }
+}
+
+fn train_bobyqa(network: &mut NetworkMut, conf: &Conf) -> Value {
+    let variables = Variables::new(network, conf);
+    let mut values = variables.values();
+    if values.is_empty() {
+        let error = variables.error(network, &values, conf.error_conf);
+        variables.write(network, &values);
+        return error;
+    }
+    let mut lower_bound = variables.bounds.iter().map(|x| x.lower).collect::<Vec<_>>();
+    let mut upper_bound = variables.bounds.iter().map(|x| x.upper).collect::<Vec<_>>();
+    if values.len() == 1 {
+        // BOBYQA needs at least two variables, the padding one is ignored by the error function.
+        values.push(0.0);
+        lower_bound.push(0.0);
+        upper_bound.push(1.0);
+    }
+    let error = {
+        let error_function = |values: &[Value]| variables.error(network, values, conf.error_conf);
+        let variables_count = values.len();
         Bobyqa::new()
             .variables_count(variables_count)
 //            .number_of_interpolation_conditions((variables_count + 1)*(variables_count + 2)/2)
@@ -41,35 +220,105 @@ impl<'network> Train for NetworkMut<'network> {
             .lower_bound(&lower_bound[..])
             .upper_bound(&upper_bound[..])
             .max_function_calls_count(conf.max_function_calls_count)
-            .perform(self.weights.values(), &error_function)
-    }
+            .perform(&mut values[..], &error_function)
+    };
+    variables.write(network, &values);
+    error
+}
+
+fn train_gradient(network: &mut NetworkMut, conf: &Conf, gradient_conf: &GradientConf) -> Value {
+    let variables = Variables::new(network, conf);
+    let mut values = variables.values();
+    let error = {
+        let error_function = |values: &[Value], samples: &[Sample]| {
+            let error_conf = error::Conf {
+                apply_conf: conf.error_conf.apply_conf,
+                samples: samples,
+                metric: conf.error_conf.metric,
+                custom_activations: conf.error_conf.custom_activations,
+            };
+            variables.error(network, values, &error_conf)
+        };
+        let batch_size = if gradient_conf.batch_size > 0 {
+            gradient_conf.batch_size
+        } else {
+            conf.error_conf.samples.len()
+        };
+        let mut first_moments = vec![0.0; values.len()];
+        let mut second_moments = vec![0.0; values.len()];
+        let mut steps_count = 0;
+        for _ in 0..gradient_conf.epochs_count {
+            for samples in conf.error_conf.samples.chunks(batch_size.max(1)) {
+                let gradient = (0..values.len())
+                    .map(|n| {
+                        let mut shifted = values.clone();
+                        shifted[n] = values[n] + gradient_conf.step;
+                        let forward = error_function(&shifted[..], samples);
+                        shifted[n] = values[n] - gradient_conf.step;
+                        let backward = error_function(&shifted[..], samples);
+                        (forward - backward) / (2.0 * gradient_conf.step)
+                    })
+                    .collect::<Vec<_>>();
+                steps_count += 1;
+                for n in 0..values.len() {
+                    let delta = match gradient_conf.optimizer {
+                        Optimizer::Sgd => gradient_conf.learning_rate * gradient[n],
+                        Optimizer::Adam => {
+                            const BETA1: Value = 0.9;
+                            const BETA2: Value = 0.999;
+                            const EPSILON: Value = 1e-8;
+                            first_moments[n] = BETA1 * first_moments[n] + (1.0 - BETA1) * gradient[n];
+                            second_moments[n] = BETA2 * second_moments[n]
+                                + (1.0 - BETA2) * gradient[n] * gradient[n];
+                            let first = first_moments[n] / (1.0 - BETA1.powi(steps_count));
+                            let second = second_moments[n] / (1.0 - BETA2.powi(steps_count));
+                            gradient_conf.learning_rate * first / (second.sqrt() + EPSILON)
+                        },
+                    };
+                    values[n] = variables.bounds[n].clamp(values[n] - delta);
+                }
+            }
+        }
+        error_function(&values[..], conf.error_conf.samples)
+    };
+    variables.write(network, &values);
+    error
 }
 
 #[test]
 fn test_train_should_succeed() {
     use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
     use neural_network::common::Node;
-    use neural_network::matrix::MatrixMut;
+    use neural_network::matrix::Matrix;
     use neural_network::apply::{Conf as ApplyConf};
-    use neural_network::error::{Conf as ErrorConf, Sample};
-    let mut weights_values = [
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
         0.1, 0.1,
         0.1, 0.1,
     ];
     let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
     let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
     {
-        let weights = MatrixMut::new(2, &mut weights_values);
+        let weights = weights_buf.as_matrix_mut();
         let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
         let mut network = NetworkMut {
             inputs: &inputs,
             outputs: &outputs,
             weights: weights,
-            nodes: &nodes
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
         };
         let apply_conf = ApplyConf {
             group_size: 1000,
             threshold: 1e-4,
+            mode: None,
+            max_visits: None,
         };
         let samples = [
             Sample {input: &[0.5], output: &[0.4]},
@@ -77,16 +326,646 @@ fn test_train_should_succeed() {
         let error_conf = ErrorConf {
             apply_conf: &apply_conf,
             samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
         };
         let conf = Conf {
             error_conf: &error_conf,
             max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds {lower: 0.0, upper: 1.0},
+            bias_bounds: Bounds {lower: 0.0, upper: 0.0},
+            arc_bounds: HashMap::new(),
         };
         let error = network.train(&conf);
         assert_eq!(error, 0.0000000024013835364655733);
     }
-    assert_eq!(weights_values, [
+    assert_eq!(weights_buf.as_matrix().values(), &[
         0.7500085532432279, 0.9999933043615582,
         0.986926343197382, 0.9995644922815343,
     ]);
+    assert_eq!(biases, vec![0.0; 2]);
+}
+
+#[test]
+fn test_train_should_fit_output_bias() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    let error = {
+        let weights = weights_buf.as_matrix_mut();
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights,
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.0], output: &[0.3]},
+            Sample {input: &[0.5], output: &[0.5]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 200,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::default(),
+            arc_bounds: HashMap::new(),
+        };
+        network.train(&conf)
+    };
+    assert!(error < 1e-2);
+    assert_eq!(biases[0], 0.0);
+    assert!((biases[1] - 0.3).abs() < 1e-2);
+    assert!((weights_buf.as_matrix().values()[0] - 0.4).abs() < 1e-2);
+}
+
+#[test]
+fn test_train_with_sgd_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    {
+        let weights = weights_buf.as_matrix_mut();
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights,
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[0.4]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Mse,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 0,
+            method: Method::Gradient(GradientConf {
+                optimizer: Optimizer::Sgd,
+                learning_rate: 0.1,
+                batch_size: 1,
+                epochs_count: 100,
+                step: 1e-6,
+            }),
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        let error = network.train(&conf);
+        assert!(error < 1e-12);
+    }
+    assert_eq!(weights_buf.as_matrix().values().len(), 1);
+}
+
+#[test]
+fn test_train_with_adam_should_reduce_error() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    {
+        let weights = weights_buf.as_matrix_mut();
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights,
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[0.4]},
+            Sample {input: &[0.25], output: &[0.3]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Mse,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 0,
+            method: Method::Gradient(GradientConf {
+                optimizer: Optimizer::Adam,
+                learning_rate: 1e-2,
+                batch_size: 1,
+                epochs_count: 200,
+                step: 1e-6,
+            }),
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        let error = network.train(&conf);
+        assert!(error < 1e-4);
+    }
+    assert_eq!(weights_buf.as_matrix().values().len(), 1);
+}
+
+#[test]
+fn test_train_network_with_single_arc_should_succeed() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0, 1].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    let error = {
+        let weights = weights_buf.as_matrix_mut();
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights,
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5, 0.0], output: &[0.2]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        network.train(&conf)
+    };
+    assert!(error < 1e-3);
+    assert!((weights_buf.as_matrix().values()[0] - 0.4).abs() < 1e-2);
+    assert_eq!(biases, vec![0.0; 2]);
+}
+
+#[test]
+fn test_train_network_without_variables_should_return_error() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let inputs = [0, 1].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_entries(2, Vec::new());
+    let mut biases = vec![0.0; 2];
+    let weights = weights_buf.as_matrix_mut();
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let mut network = NetworkMut {
+        inputs: &inputs,
+        outputs: &outputs,
+        weights: weights,
+        nodes: &nodes,
+        activations: &activations,
+        biases: &mut biases,
+    };
+    let apply_conf = ApplyConf {
+        group_size: 1000,
+        threshold: 1e-4,
+        mode: None,
+        max_visits: None,
+    };
+    let samples = [
+        Sample {input: &[0.5, 0.3], output: &[0.2]},
+    ];
+    let error_conf = ErrorConf {
+        apply_conf: &apply_conf,
+        samples: &samples,
+        metric: &MetricFunction::Euclidean,
+        custom_activations: &HashMap::new(),
+    };
+    let conf = Conf {
+        error_conf: &error_conf,
+        max_function_calls_count: 71,
+        method: Method::Bobyqa,
+        allow_new_arcs: false,
+        weight_bounds: Bounds::default(),
+        bias_bounds: Bounds::symmetric(),
+        arc_bounds: HashMap::new(),
+    };
+    let error = network.train(&conf);
+    assert_eq!(error, (0.3 - 0.2 as Value).abs());
+}
+
+#[test]
+fn test_train_network_with_negative_arc_and_default_bounds_should_keep_arc_negative() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::mutator::Mutator;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, -0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let error = {
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights_buf.as_matrix_mut(),
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[-0.2]},
+            Sample {input: &[1.0], output: &[-0.4]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 200,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::default(),
+            arc_bounds: HashMap::new(),
+        };
+        network.train(&conf)
+    };
+    assert!(error < 1e-2);
+    assert!(weights_buf.as_matrix().values()[0] < 0.0);
+    let network = Network {
+        inputs: &inputs,
+        outputs: &outputs,
+        weights: weights_buf.as_matrix(),
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
+    };
+    let mutator = Mutator::from_network(&network);
+    assert_eq!(mutator.arcs().count(), 1);
+}
+
+#[test]
+fn test_train_without_new_arcs_should_keep_structure() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1, 0.0,
+        0.0, 0.0, 0.1,
+        0.0, 0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [2].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(3, &weights_values));
+    let mut biases = vec![0.0; 3];
+    {
+        let weights = weights_buf.as_matrix_mut();
+        let nodes = (0..3).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 3];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights,
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[0.4]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        network.train(&conf);
+    }
+    let weights = weights_buf.as_matrix();
+    assert_eq!(weights.values().len(), 2);
+    assert!(weights.get(0, 1).is_some());
+    assert!(weights.get(1, 2).is_some());
+}
+
+#[test]
+fn test_train_with_new_arcs_should_keep_only_nonzero_arcs() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+    let activations = vec![ActivationFunction::Identity; 2];
+    let apply_conf = ApplyConf {
+        group_size: 1000,
+        threshold: 1e-4,
+        mode: None,
+        max_visits: None,
+    };
+    let samples = [
+        Sample {input: &[0.5], output: &[0.4]},
+        Sample {input: &[0.25], output: &[0.2]},
+    ];
+    let error_conf = ErrorConf {
+        apply_conf: &apply_conf,
+        samples: &samples,
+        metric: &MetricFunction::Euclidean,
+        custom_activations: &HashMap::new(),
+    };
+    let initial_error = Network {
+        inputs: &inputs,
+        outputs: &outputs,
+        weights: weights_buf.as_matrix(),
+        nodes: &nodes,
+        activations: &activations,
+        biases: &biases,
+    }.error(&error_conf);
+    {
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights_buf.as_matrix_mut(),
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: true,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        assert!(network.train(&conf) < initial_error);
+    }
+    let weights = weights_buf.as_matrix();
+    assert!(weights.get(0, 1).is_some());
+    assert!(weights.values().iter().all(|&x| x != 0.0));
+}
+
+#[test]
+fn test_train_with_negative_weight_bounds_should_learn_inhibitory_arc() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.1,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    {
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights_buf.as_matrix_mut(),
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[-0.4]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds {lower: -1.0, upper: 1.0},
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: HashMap::new(),
+        };
+        assert!(network.train(&conf) < 0.1);
+    }
+    assert!(*weights_buf.as_matrix().get(0, 1).unwrap() < 0.0);
+}
+
+#[test]
+fn test_train_with_arc_bounds_should_keep_arc_weight_within_them() {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use neural_network::sparse_matrix::SparseMatrixBuf;
+    use neural_network::activation::ActivationFunction;
+    use neural_network::common::Node;
+    use neural_network::matrix::Matrix;
+    use neural_network::apply::{Conf as ApplyConf};
+    use neural_network::error::{Conf as ErrorConf, MetricFunction, Sample};
+    let weights_values = [
+        0.0, 0.25,
+        0.0, 0.0,
+    ];
+    let inputs = [0].iter().cloned().collect::<BTreeSet<usize>>();
+    let outputs = [1].iter().cloned().collect::<HashSet<usize>>();
+    let mut weights_buf = SparseMatrixBuf::from_matrix(&Matrix::new(2, &weights_values));
+    let mut biases = vec![0.0; 2];
+    {
+        let nodes = (0..2).map(|x| (x, Node(x))).collect::<HashMap<usize, Node>>();
+        let activations = vec![ActivationFunction::Identity; 2];
+        let mut network = NetworkMut {
+            inputs: &inputs,
+            outputs: &outputs,
+            weights: weights_buf.as_matrix_mut(),
+            nodes: &nodes,
+            activations: &activations,
+            biases: &mut biases,
+        };
+        let apply_conf = ApplyConf {
+            group_size: 1000,
+            threshold: 1e-4,
+            mode: None,
+            max_visits: None,
+        };
+        let samples = [
+            Sample {input: &[0.5], output: &[0.4]},
+            Sample {input: &[0.25], output: &[0.2]},
+        ];
+        let error_conf = ErrorConf {
+            apply_conf: &apply_conf,
+            samples: &samples,
+            metric: &MetricFunction::Euclidean,
+            custom_activations: &HashMap::new(),
+        };
+        let arc_bounds = [(Arc(Node(0), Node(1)), Bounds {lower: 0.2, upper: 0.3})]
+            .iter().cloned().collect::<HashMap<_, _>>();
+        let conf = Conf {
+            error_conf: &error_conf,
+            max_function_calls_count: 71,
+            method: Method::Bobyqa,
+            allow_new_arcs: false,
+            weight_bounds: Bounds::default(),
+            bias_bounds: Bounds::symmetric(),
+            arc_bounds: arc_bounds,
+        };
+        network.train(&conf);
+    }
+    let weight = *weights_buf.as_matrix().get(0, 1).unwrap();
+    assert!(0.2 <= weight && weight <= 0.3);
 }
@@ -5,20 +5,27 @@ extern crate rustc_serialize;
 
 use rustc_serialize::json;
 
-use gannai::neural_network::{ApplyConf, Network};
+use gannai::neural_network::{ApplyConf, ApplyMode, Network};
 use gannai::tools::common::{make_conf, make_network_buf};
 
 struct Args {
     conf: String,
     network: String,
+    batch_size: usize,
 }
 
 fn main() {
-    let mut args = Args {conf: String::new(), network: String::new()};
+    let mut args = Args {conf: String::new(), network: String::new(), batch_size: 1};
     parse_args(&mut args);
     let conf = make_conf::<ApplyConf>(&args.conf);
     let network_buf = make_network_buf(&args.network);
-    apply(&conf, &network_buf.as_network());
+    if let Some(ApplyMode::Recurrent) = conf.mode {
+        apply_recurrent(&network_buf.as_network());
+    } else if let Some(ApplyMode::MonteCarlo(_)) = conf.mode {
+        apply_monte_carlo(&conf, &network_buf.as_network());
+    } else {
+        apply(&conf, &network_buf.as_network(), args.batch_size);
+    }
 }
 
 fn parse_args(args: &mut Args) {
@@ -29,23 +36,70 @@ fn parse_args(args: &mut Args) {
         .add_argument("conf", Store, "Path to conf json file").required();
     parser.refer(&mut args.network)
         .add_argument("network", Store, "Path to neural network json file").required();
+    parser.refer(&mut args.batch_size)
+        .add_option(&["--batch-size"], Store, "Number of inputs to read and apply in parallel before writing results (default is 1)");
     parser.parse_args_or_exit();
 }
 
-fn apply(conf: &ApplyConf, network: &Network) {
+fn apply(conf: &ApplyConf, network: &Network, batch_size: usize) {
     use std::io::{BufRead, stdin};
     use gannai::neural_network::Apply;
+    assert!(batch_size > 0);
     let application = network.apply(&conf);
+    application.check().unwrap();
+    let file = stdin();
+    let mut lines = file.lock().lines();
+    loop {
+        let batch = lines.by_ref()
+            .take(batch_size)
+            .map(|line| json::decode::<Input>(&line.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        let result = {
+            let inputs = batch.iter().map(|x| &x.input[..]).collect::<Vec<_>>();
+            application.par_perform_batch(&inputs[..])
+        };
+        for (n, input) in batch.into_iter().enumerate() {
+            let result_values = result.as_matrix().row(n).to_vec();
+            let output = Output {input: input.input, output: input.output, result: result_values};
+            println!("{}", json::encode(&output).unwrap());
+        }
+    }
+}
+
+fn apply_recurrent(network: &Network) {
+    use std::io::{BufRead, stdin};
+    use gannai::neural_network::Apply;
+    let mut application = network.apply_recurrent();
     let file = stdin();
     for line in file.lock().lines() {
-        let data = line.unwrap();
-        let input: Input = json::decode(&data).unwrap();
-        let result_values = application.perform(&input.input[..]);
+        let input: Input = json::decode(&line.unwrap()).unwrap();
+        let result_values = application.step(&input.input[..]);
         let output = Output {input: input.input, output: input.output, result: result_values};
         println!("{}", json::encode(&output).unwrap());
     }
 }
 
+fn apply_monte_carlo(conf: &ApplyConf, network: &Network) {
+    use std::io::{BufRead, stdin};
+    use gannai::neural_network::Apply;
+    let application = network.apply(&conf);
+    let file = stdin();
+    for line in file.lock().lines() {
+        let input: Input = json::decode(&line.unwrap()).unwrap();
+        let estimate = application.perform_estimate(&input.input[..]);
+        let output = EstimateOutput {
+            input: input.input,
+            output: input.output,
+            result: estimate.mean,
+            variance: estimate.variance,
+        };
+        println!("{}", json::encode(&output).unwrap());
+    }
+}
+
 #[derive(RustcDecodable)]
 struct Input {
     input: Vec<f64>,
@@ -58,3 +112,11 @@ struct Output {
     output: Option<Vec<f64>>,
     result: Vec<f64>,
 }
+
+#[derive(RustcEncodable)]
+struct EstimateOutput {
+    input: Vec<f64>,
+    output: Option<Vec<f64>>,
+    result: Vec<f64>,
+    variance: Vec<f64>,
+}
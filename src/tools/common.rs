@@ -1,9 +1,10 @@
 extern crate rustc_serialize;
 
+use std::collections::HashMap;
 use std::io::{BufRead, Lines};
 use self::rustc_serialize::{Decodable, json};
 
-use super::super::neural_network::NetworkBuf;
+use super::super::neural_network::{Arc, Checkpoint, NetworkBuf, Node, TrainBounds};
 
 #[derive(RustcDecodable)]
 pub struct Sample {
@@ -11,6 +12,23 @@ pub struct Sample {
     pub output: Vec<f64>,
 }
 
+#[derive(RustcDecodable)]
+pub struct ArcBounds {
+    pub src: usize,
+    pub dst: usize,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+pub fn make_arc_bounds(arcs_bounds: &Option<Vec<ArcBounds>>) -> HashMap<Arc, TrainBounds> {
+    match *arcs_bounds {
+        Some(ref arcs_bounds) => arcs_bounds.iter()
+            .map(|x| (Arc(Node(x.src), Node(x.dst)), TrainBounds {lower: x.lower, upper: x.upper}))
+            .collect::<_>(),
+        None => HashMap::new(),
+    }
+}
+
 pub fn make_conf<T: Decodable>(file_path: &str) -> T {
     use std::io::Read;
     use std::fs::File;
@@ -27,6 +45,14 @@ pub fn make_network_buf(file_path: &str) -> NetworkBuf {
     json::decode(&data).unwrap()
 }
 
+pub fn make_checkpoint(file_path: &str) -> Checkpoint {
+    use std::io::Read;
+    use std::fs::File;
+    let mut data = String::new();
+    File::open(file_path).unwrap().read_to_string(&mut data).unwrap();
+    json::decode(&data).unwrap()
+}
+
 pub fn make_samples(file_path: &str) -> Vec<Sample> {
     use std::io::{BufReader, stdin};
     use std::fs::File;
@@ -5,37 +5,103 @@ extern crate rustc_serialize;
 
 use rustc_serialize::json;
 
-use gannai::neural_network::NetworkBuf;
-use gannai::tools::common::{Sample, make_conf, make_samples, make_network_buf};
+use gannai::neural_network::{ApplyMode, Checkpoint, CrossoverFunction, FitnessFunction,
+                             MetricFunction, MutationConf, NetworkBuf, SelectionFunction,
+                             SpeciationConf, Stats, TrainBounds};
+use gannai::tools::common::{ArcBounds, Sample, make_arc_bounds, make_checkpoint, make_conf,
+                            make_samples, make_network_buf};
 
 struct Args {
     conf: String,
     samples: String,
     network: String,
+    validation: String,
+    checkpoint: String,
+    resume: String,
+    stats: String,
+    front: String,
+    seed: Option<u32>,
+    random_seed: bool,
 }
 
 #[derive(RustcDecodable)]
 struct Conf {
     group_size: usize,
     threshold: f64,
+    mode: Option<ApplyMode>,
+    max_visits: Option<usize>,
     max_function_calls_count: usize,
+    metric: Option<MetricFunction>,
+    weight_bounds: Option<TrainBounds>,
+    bias_bounds: Option<TrainBounds>,
+    arc_bounds: Option<Vec<ArcBounds>>,
     error: f64,
     population_size: usize,
     iterations_count: usize,
+    patience: Option<usize>,
+    checkpoint_interval: Option<usize>,
+    mutation: Option<MutationConf>,
+    selection: Option<SelectionFunction>,
+    elitism_count: Option<usize>,
+    crossover: Option<CrossoverFunction>,
+    fitness: Option<FitnessFunction>,
+    speciation: Option<SpeciationConf>,
 }
 
 fn main() {
-    let mut args = Args {conf: String::new(), samples: String::new(), network: String::new()};
+    let mut args = Args {
+        conf: String::new(),
+        samples: String::new(),
+        network: String::new(),
+        validation: String::new(),
+        checkpoint: String::new(),
+        resume: String::new(),
+        stats: String::new(),
+        front: String::new(),
+        seed: None,
+        random_seed: false,
+    };
     parse_args(&mut args);
+    let seed = make_seed(args.seed, args.random_seed);
     let conf = make_conf(&args.conf);
     let network_buf = make_network_buf(&args.network);
     let samples = make_samples(&args.samples);
-    let evolved_network_buf = evolve(&conf, &samples, network_buf);
+    let validation_samples = if args.validation.is_empty() {
+        Vec::new()
+    } else {
+        make_samples(&args.validation)
+    };
+    let resume_from = if args.resume.is_empty() {
+        None
+    } else {
+        Some(make_checkpoint(&args.resume))
+    };
+    let evolved_network_buf = evolve(&conf, &samples, &validation_samples, &args.checkpoint,
+                                     resume_from.as_ref(), &args.stats, &args.front, seed,
+                                     network_buf);
     println!("{}", json::encode(&evolved_network_buf).unwrap());
 }
 
+fn make_seed(seed: Option<u32>, random_seed: bool) -> u32 {
+    use std::io::{Write, stderr};
+    let result = if random_seed {
+        assert!(seed.is_none(), "--seed and --random-seed are mutually exclusive");
+        loop {
+            let value = rand::random::<u32>();
+            if value != 0 {
+                break value;
+            }
+        }
+    } else {
+        seed.unwrap_or(1)
+    };
+    assert!(result != 0, "Seed must be nonzero");
+    writeln!(stderr(), "Seed: {}", result).unwrap();
+    result
+}
+
 fn parse_args(args: &mut Args) {
-    use argparse::{ArgumentParser, Store};
+    use argparse::{ArgumentParser, Store, StoreOption, StoreTrue};
     let mut parser = ArgumentParser::new();
     parser.set_description("Evolves neural network by using samples");
     parser.refer(&mut args.conf)
@@ -44,10 +110,28 @@ fn parse_args(args: &mut Args) {
         .add_argument("network", Store, "Path to neural network json file").required();
     parser.refer(&mut args.samples)
         .add_argument("samples", Store, "Path to samples json file (default is stdin)");
+    parser.refer(&mut args.validation)
+        .add_option(&["--validation"], Store, "Path to validation samples json file");
+    parser.refer(&mut args.checkpoint)
+        .add_option(&["--checkpoint"], Store, "Path to checkpoint json file to write");
+    parser.refer(&mut args.resume)
+        .add_option(&["--resume"], Store, "Path to checkpoint json file to resume from");
+    parser.refer(&mut args.stats)
+        .add_option(&["--stats"], Store, "Path to per-generation stats json lines file to write");
+    parser.refer(&mut args.front)
+        .add_option(&["--front"], Store, "Path to directory to write pareto front networks json files");
+    parser.refer(&mut args.seed)
+        .add_option(&["--seed"], StoreOption, "Nonzero random generator seed (default is 1)");
+    parser.refer(&mut args.random_seed)
+        .add_option(&["--random-seed"], StoreTrue, "Use random generator seed from entropy source");
     parser.parse_args_or_exit();
 }
 
-fn evolve(conf: &Conf, src_samples: &[Sample], network_buf: NetworkBuf) -> NetworkBuf {
+fn evolve(conf: &Conf, src_samples: &[Sample], src_validation_samples: &[Sample],
+          checkpoint_path: &str, resume_from: Option<&Checkpoint>, stats_path: &str,
+          front_path: &str, seed: u32, network_buf: NetworkBuf) -> NetworkBuf {
+    use std::collections::HashMap;
+    use std::fs::{File, create_dir_all, rename};
     use std::io::{Write, stderr};
     use rand::{XorShiftRng, SeedableRng};
     use gannai::neural_network::{
@@ -56,42 +140,125 @@ fn evolve(conf: &Conf, src_samples: &[Sample], network_buf: NetworkBuf) -> Netwo
         ErrorConf,
         Evolve,
         EvolveConf,
+        EvolveFront,
         IdGenerator,
         Mutator,
         Sample,
         TrainConf,
+        TrainMethod,
     };
     let mut node_id = IdGenerator::new(0);
     let apply_conf = ApplyConf {
         group_size: conf.group_size,
         threshold: conf.threshold,
+        mode: conf.mode.clone(),
+        max_visits: conf.max_visits,
     };
     let samples: Vec<Sample> = src_samples.iter()
         .map(|x| Sample {input: &x.input[..], output: &x.output[..]})
         .collect::<_>();
+    let metric = conf.metric.clone().unwrap_or_default();
+    let custom_activations = HashMap::new();
     let error_conf = ErrorConf {
         apply_conf: &apply_conf,
         samples: &samples[..],
+        metric: &metric,
+        custom_activations: &custom_activations,
+    };
+    let validation_samples: Vec<Sample> = src_validation_samples.iter()
+        .map(|x| Sample {input: &x.input[..], output: &x.output[..]})
+        .collect::<_>();
+    let validation_error_conf = ErrorConf {
+        apply_conf: &apply_conf,
+        samples: &validation_samples[..],
+        metric: &metric,
+        custom_activations: &custom_activations,
     };
     let train_conf = TrainConf {
         error_conf: &error_conf,
         max_function_calls_count: conf.max_function_calls_count,
+        method: TrainMethod::Bobyqa,
+        allow_new_arcs: false,
+        weight_bounds: conf.weight_bounds.unwrap_or_default(),
+        bias_bounds: conf.bias_bounds.unwrap_or_else(TrainBounds::symmetric),
+        arc_bounds: make_arc_bounds(&conf.arc_bounds),
     };
+    let mutation_conf = conf.mutation.clone().unwrap_or_default();
+    let selection = conf.selection.clone().unwrap_or_default();
+    let crossover = conf.crossover.clone().unwrap_or_default();
+    let fitness = conf.fitness.clone().unwrap_or_default();
     let mut rng = XorShiftRng::new_unseeded();
-    rng.reseed([1, 1, 1, 1]);
+    rng.reseed([seed, seed, seed, seed]);
+    let mut write_checkpoint = |checkpoint: &Checkpoint| {
+        let tmp_path = format!("{}.tmp", checkpoint_path);
+        File::create(&tmp_path).unwrap()
+            .write_all(json::encode(checkpoint).unwrap().as_bytes()).unwrap();
+        rename(&tmp_path, checkpoint_path).unwrap();
+        writeln!(stderr(), "Checkpoint {} iterations written", checkpoint.iterations_count).unwrap();
+    };
+    let mut stats_file = if stats_path.is_empty() {
+        None
+    } else {
+        Some(File::create(stats_path).unwrap())
+    };
+    let mut write_stats = |stats: &Stats| {
+        if let Some(ref mut file) = stats_file {
+            writeln!(file, "{}", json::encode(stats).unwrap()).unwrap();
+        }
+    };
     let mut evolve_conf = EvolveConf {
         train_conf: &train_conf,
+        mutation_conf: &mutation_conf,
+        selection: &selection,
+        crossover: &crossover,
+        elitism_count: conf.elitism_count.unwrap_or(0),
+        fitness: &fitness,
         rng: &mut rng,
         node_id: &mut node_id,
         population_size: conf.population_size,
         error: conf.error,
         iterations_count: conf.iterations_count,
+        validation_error_conf: if validation_samples.is_empty() {
+            None
+        } else {
+            Some(&validation_error_conf)
+        },
+        patience: conf.patience,
+        checkpoint_interval: conf.checkpoint_interval.unwrap_or(0),
+        checkpoint_writer: if checkpoint_path.is_empty() {
+            None
+        } else {
+            Some(&mut write_checkpoint)
+        },
+        resume_from: resume_from,
+        observer: if stats_path.is_empty() {
+            None
+        } else {
+            Some(&mut write_stats)
+        },
+        speciation_conf: conf.speciation.as_ref(),
     };
     let network = network_buf.as_network();
     let initial_error = network.error(&error_conf);
     writeln!(stderr(), "Initial error: {}", initial_error).unwrap();
-    let result = Mutator::from_network(&network).evolve(&mut evolve_conf).as_network_buf();
+    let result = if front_path.is_empty() {
+        Mutator::from_network(&network).evolve(&mut evolve_conf).as_network_buf()
+    } else {
+        let front = Mutator::from_network(&network).evolve_front(&mut evolve_conf);
+        create_dir_all(front_path).unwrap();
+        for (n, x) in front.iter().enumerate() {
+            let network_buf = x.as_network_buf();
+            writeln!(stderr(), "Front {} error: {}, nodes: {}, arcs: {}", n,
+                     network_buf.as_network().error(&error_conf), x.nodes().len(), x.arcs().len()).unwrap();
+            File::create(format!("{}/{}.json", front_path, n)).unwrap()
+                .write_all(json::encode(&network_buf).unwrap().as_bytes()).unwrap();
+        }
+        front[0].as_network_buf()
+    };
     let final_error = result.as_network().error(&error_conf);
     writeln!(stderr(), "Final error: {} (improved by {} times)", final_error, initial_error / final_error).unwrap();
+    if !validation_samples.is_empty() {
+        writeln!(stderr(), "Final validation error: {}", result.as_network().error(&validation_error_conf)).unwrap();
+    }
     result
 }
@@ -4,8 +4,9 @@ extern crate rustc_serialize;
 
 use rustc_serialize::json;
 
-use gannai::neural_network::NetworkBuf;
-use gannai::tools::common::{Sample, make_conf, make_samples, make_network_buf};
+use gannai::neural_network::{ApplyMode, MetricFunction, NetworkBuf, Optimizer, TrainBounds};
+use gannai::tools::common::{ArcBounds, Sample, make_arc_bounds, make_conf, make_samples,
+                            make_network_buf};
 
 struct Args {
     conf: String,
@@ -17,7 +18,18 @@ struct Args {
 struct Conf {
     group_size: usize,
     threshold: f64,
+    mode: Option<ApplyMode>,
+    max_visits: Option<usize>,
     max_function_calls_count: usize,
+    metric: Option<MetricFunction>,
+    optimizer: Option<Optimizer>,
+    learning_rate: Option<f64>,
+    batch_size: Option<usize>,
+    epochs_count: Option<usize>,
+    allow_new_arcs: Option<bool>,
+    weight_bounds: Option<TrainBounds>,
+    bias_bounds: Option<TrainBounds>,
+    arc_bounds: Option<Vec<ArcBounds>>,
 }
 
 fn main() {
@@ -44,27 +56,50 @@ fn parse_args(args: &mut Args) {
 }
 
 fn train(conf: &Conf, src_samples: &[Sample], network_buf: &mut NetworkBuf) {
+    use std::collections::HashMap;
     use gannai::neural_network::{
         ApplyConf,
         ErrorConf,
+        GradientConf,
         Sample,
         Train,
         TrainConf,
+        TrainMethod,
     };
     let apply_conf = ApplyConf {
         group_size: conf.group_size,
         threshold: conf.threshold,
+        mode: conf.mode.clone(),
+        max_visits: conf.max_visits,
     };
     let samples: Vec<Sample> = src_samples.iter()
         .map(|x| Sample {input: &x.input[..], output: &x.output[..]})
         .collect::<_>();
+    let metric = conf.metric.clone().unwrap_or_default();
     let error_conf = ErrorConf {
         apply_conf: &apply_conf,
         samples: &samples[..],
+        metric: &metric,
+        custom_activations: &HashMap::new(),
+    };
+    let method = match conf.optimizer {
+        Some(ref optimizer) => TrainMethod::Gradient(GradientConf {
+            optimizer: optimizer.clone(),
+            learning_rate: conf.learning_rate.unwrap_or(1e-2),
+            batch_size: conf.batch_size.unwrap_or(samples.len()),
+            epochs_count: conf.epochs_count.unwrap_or(100),
+            step: 1e-6,
+        }),
+        None => TrainMethod::Bobyqa,
     };
     let train_conf = TrainConf {
         error_conf: &error_conf,
         max_function_calls_count: conf.max_function_calls_count,
+        method: method,
+        allow_new_arcs: conf.allow_new_arcs.unwrap_or(false),
+        weight_bounds: conf.weight_bounds.unwrap_or_default(),
+        bias_bounds: conf.bias_bounds.unwrap_or_else(TrainBounds::symmetric),
+        arc_bounds: make_arc_bounds(&conf.arc_bounds),
     };
     network_buf.as_network_mut().train(&train_conf);
 }